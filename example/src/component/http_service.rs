@@ -1,6 +1,6 @@
 use std::{rc::Rc, path::PathBuf, error::Error};
 
-use crate::component::response::{HttpResponseBuilder, Status};
+use http_server::static_files::StaticFiles;
 
 use super::{request::HttpRequest, response::HttpResponse, router::Router, router_builder::PathSegment};
 
@@ -14,7 +14,9 @@ impl HttpService for Box<dyn HttpService> {
     }
 }
 
-pub struct ServeFileService(PathBuf);
+pub struct ServeFileService {
+    files: StaticFiles,
+}
 
 impl  HttpService for fn(&HttpRequest) -> HttpResponse {
     fn handle(&mut self, request: &HttpRequest) -> Result<Rc<HttpResponse>, Box<dyn Error>> {
@@ -29,46 +31,21 @@ impl  HttpService for fn(&HttpRequest) -> Rc<HttpResponse> {
 }
 
 impl ServeFileService {
-    pub fn new(path: PathBuf) -> Self {
-        ServeFileService(path)
+    pub fn new(root: PathBuf) -> Self {
+        ServeFileService { files: StaticFiles::new(root) }
+    }
+}
+
+impl From<StaticFiles> for ServeFileService {
+    fn from(files: StaticFiles) -> Self {
+        ServeFileService { files }
     }
 }
 
 impl  HttpService for ServeFileService {
     fn handle(&mut self, request: &HttpRequest) -> Result<Rc<HttpResponse>, Box<dyn Error>> {
-        let path_segments = request.path().split("/").filter(|x| x.len() > 0);
-
-        let mut path = self.0.to_path_buf();
-        for segment in path_segments {
-            path = path.join(segment);
-        }
-
-        let os_path = path.as_path();
-
-        if !os_path.starts_with(self.0.as_path()) {
-            return Ok(Rc::new(HttpResponseBuilder::new()
-                .status(Status::Forbidden)
-                .body(
-                    "403 Forbidden\n\nReason: Attempted to read a file outside the server sandbox!",
-                )
-                .build()));
-        }
-
-        use std::fs::read_to_string;
-        let file = read_to_string(os_path);
-
-        let response = match file {
-            Ok(file) => HttpResponseBuilder::new()
-                .status(Status::Ok)
-                .body(file)
-                .build(),
-            Err(err) => HttpResponseBuilder::new()
-                .status(Status::NotFound)
-                .body(format!("{err:#?}"))
-                .build(),
-        };
-
-        return Ok(Rc::new(response));
+        let response = self.files.respond(request.path(), request.query(), request.headers());
+        Ok(Rc::new(HttpResponse::new(response.status, response.headers, Box::new(response.body))))
     }
 }
 
@@ -1,26 +1,27 @@
-use std::collections::HashMap;
+use std::{cell::OnceCell, collections::HashMap};
 
-pub enum Method {
-    GET,
-    POST,
-}
+use http_server::{cookie::CookieJar, form::{self, FormError}, from_query::FromQuery, header::HeaderMap, json::{self, JsonBodyError, JsonValue}, multipart::{Multipart, MultipartError}};
+
+pub use http_server::request::Method;
 
 pub struct HttpRequest<'a> {
     method: &'a Method,
     path: &'a String,
-    query: &'a HashMap<String, String>,
-    headers: &'a HashMap<String, String>,
+    query: &'a HashMap<String, Vec<String>>,
+    headers: &'a HeaderMap,
     body: &'a Vec<u8>,
+    cookies: OnceCell<CookieJar>,
 }
 
 impl <'a> HttpRequest<'a> {
-    pub fn new(method: &'a Method, path: &'a String, query: &'a HashMap<String, String>, headers: &'a HashMap<String, String>, body: &'a Vec<u8>) -> Self {
+    pub fn new(method: &'a Method, path: &'a String, query: &'a HashMap<String, Vec<String>>, headers: &'a HeaderMap, body: &'a Vec<u8>) -> Self {
         HttpRequest { 
             method, 
             path,
             query,
             headers,
-            body
+            body,
+            cookies: OnceCell::new(),
         }
     }
 
@@ -32,15 +33,35 @@ impl <'a> HttpRequest<'a> {
         self.path
     }
 
-    pub fn query(&self) -> &HashMap<String, String> {
+    pub fn query(&self) -> &HashMap<String, Vec<String>> {
         self.query
     }
 
-    pub fn headers(&self) -> &HashMap<String, String> {
+    pub fn headers(&self) -> &HeaderMap {
         self.headers
     }
 
     pub fn body(&self) -> &Vec<u8> {
         self.body
     }
+
+    /// Decodes an `application/x-www-form-urlencoded` body
+    pub fn form<T: FromQuery>(&self) -> Result<T, FormError> {
+        form::parse_form(self.headers, self.body)
+    }
+
+    /// Parses a JSON body
+    pub fn json(&self) -> Result<JsonValue, JsonBodyError> {
+        json::parse_json(self.headers, self.body)
+    }
+
+    /// Reads a `multipart/form-data` body one part at a time
+    pub fn multipart(&self) -> Result<Multipart<&'a [u8]>, MultipartError> {
+        Multipart::from_headers(self.headers, self.body.as_slice())
+    }
+
+    /// The cookies sent with the request, parsed the first time they are asked for
+    pub fn cookies(&self) -> &CookieJar {
+        self.cookies.get_or_init(|| CookieJar::from_headers(self.headers))
+    }
 }
@@ -1,13 +1,21 @@
-use std::{error::Error, net::TcpStream, collections::HashMap, io::Write};
+use std::io::{self, Write};
+
+use http_server::cookie::SetCookie;
+use http_server::header::{HeaderMap, HeaderValue};
+use http_server::file::FileBody;
+use http_server::json::Json;
+use http_server::static_files::StaticBody;
+
+pub use http_server::status::Status;
 
 pub trait HttpBody {
-    fn write_headers(&self, headers: &mut HashMap<String, String>);
-    fn write_body(self, stream: &mut TcpStream) -> Result<(), Box<dyn Error>>;
+    fn write_headers(&self, headers: &mut HeaderMap);
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()>;
 }
 
 pub struct HttpResponse {
     status: Status,
-    headers: HashMap<String, String>,
+    headers: HeaderMap,
     body: Box<dyn HttpBody>,
 }
 
@@ -16,52 +24,49 @@ pub struct NoBody;
 
 pub struct HttpResponseBuilder<S, B> {
     status: S,
-    headers: HashMap<String, String>,
+    headers: HeaderMap,
     body: B,
 }
 
-pub enum Status {
-    // 2XX
-    Ok,
-
-    // 4XX
-    Forbidden,
-    NotFound,
-}
-
-impl Status {
-    fn code(&self) -> usize {
-        match self {
-            Self::Ok => 200,
-            Self::Forbidden => 403,
-            Self::NotFound => 404,
-        }    
-    }
-
-    fn message(&self) -> &'static str {
-        match self {
-            Self::Ok => "Ok",
-            Self::Forbidden => "Forbidden",
-            Self::NotFound => "Not Found",
-        }    
-    }
-}
-
 impl HttpResponse {
-    pub fn new(status: Status, headers: HashMap<String, String>, body: Box<dyn HttpBody>) -> Self {
+    pub fn new(status: Status, headers: HeaderMap, body: Box<dyn HttpBody>) -> Self {
         HttpResponse {
             status,
             headers,
             body,
         }
     }
+
+    pub fn write<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.body.write_headers(&mut self.headers);
+        if !self.status.allows_body() {
+            self.headers.remove("Content-Length");
+            self.headers.remove("Transfer-Encoding");
+        }
+        self.headers.validate()?;
+
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status).into_bytes();
+        for (key, value) in self.headers.iter() {
+            head.extend_from_slice(key.as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        writer.write_all(&head)?;
+
+        if self.status.allows_body() {
+            self.body.write_body(writer)?;
+        }
+        writer.flush()
+    }
 }
 
 impl HttpResponseBuilder<NoStatus, NoBody> {
     pub fn new() -> Self {
         HttpResponseBuilder { 
             status: NoStatus,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body: NoBody,
         }
     }
@@ -69,8 +74,14 @@ impl HttpResponseBuilder<NoStatus, NoBody> {
 }
 
 impl <S, B> HttpResponseBuilder<S, B> {
-    pub fn header(mut self, key: String, value: String) -> Self {
-        self.headers.insert(key, value);
+    pub fn header<K: Into<String>, V: Into<HeaderValue>>(mut self, key: K, value: V) -> Self {
+        self.headers.append(key, value);
+        self
+    }
+
+    /// Sets a cookie, each one in a `Set-Cookie` field of its own
+    pub fn cookie(mut self, cookie: SetCookie) -> Self {
+        self.headers.append("Set-Cookie", cookie.to_string());
         self
     }
 }
@@ -108,32 +119,61 @@ impl Into<HttpResponse> for HttpResponseBuilder<Status, Box<dyn HttpBody>> {
 }
 
 impl HttpBody for &str {
-    fn write_headers(&self, headers: &mut HashMap<String, String>) {
-        headers.insert("Content-Length".into(), self.as_bytes().len().to_string());
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("Content-Length", self.len().to_string());
     }
 
-    fn write_body(self, stream: &mut TcpStream) -> Result<(), Box<dyn Error>> {
-        Ok(stream.write_all(self.as_bytes())?)
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self.as_bytes())
     }
 }
 
 impl HttpBody for String {
-    fn write_headers(&self, headers: &mut HashMap<String, String>) {
-        headers.insert("Content-Length".into(), self.as_bytes().len().to_string());
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("Content-Length", self.len().to_string());
     }
 
-    fn write_body(self, stream: &mut TcpStream) -> Result<(), Box<dyn Error>> {
-        Ok(stream.write_all(self.as_bytes())?)
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self.as_bytes())
     }
 }
 
 impl HttpBody for &[u8] {
-    fn write_headers(&self, headers: &mut HashMap<String, String>) {
-        headers.insert("Content-Length".into(), self.len().to_string());
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("Content-Length", self.len().to_string());
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self)
+    }
+}
+
+impl HttpBody for Json {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        http_server::response::HttpBody::write_headers(self, headers);
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        http_server::response::HttpBody::write_body(self, writer)
+    }
+}
+
+impl HttpBody for FileBody {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        http_server::response::HttpBody::write_headers(self, headers);
     }
 
-    fn write_body(self, stream: &mut TcpStream) -> Result<(), Box<dyn Error>> {
-        Ok(stream.write_all(self)?)
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        http_server::response::HttpBody::write_body(self, writer)
     }
 }
 
+impl HttpBody for StaticBody {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        http_server::response::HttpBody::write_headers(self, headers);
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        http_server::response::HttpBody::write_body(self, writer)
+    }
+}
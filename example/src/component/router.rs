@@ -1,7 +1,9 @@
-use std::{rc::Rc, collections::HashMap, error::Error};
+use std::{rc::Rc, collections::HashMap, error::Error, fmt::Display};
+
+use http_server::request::allow_header;
 
 use crate::{
-    component::request::HttpRequest,
+    component::request::{HttpRequest, Method},
     component::response::HttpResponse,
 };
 
@@ -10,11 +12,23 @@ use super::{http_service::HttpService, response::{HttpResponseBuilder, Status}};
 pub enum Router {
     Router{
         paths: HashMap<String, Vec<Router>>,
-        default_services: Vec<Box<dyn HttpService>>,
+        default_services: Vec<Router>,
     },
     Service(Option<Method>, Box<dyn HttpService>),
 }
 
+/// Returned by a service registered for a different method than the request's
+#[derive(Debug)]
+pub struct MethodMismatch;
+
+impl Display for MethodMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The service does not handle this method")
+    }
+}
+
+impl Error for MethodMismatch {}
+
 fn compute_segments<'a>(path: &'a [char]) -> (&'a [char], &'a [char]) {
     let mut i = 0;
 
@@ -42,67 +56,93 @@ fn compute_segments<'a>(path: &'a [char]) -> (&'a [char], &'a [char]) {
 }
 
 impl Router {
-    pub fn new(paths: HashMap<String, Vec<Router>>, default_services: Vec<Box<dyn HttpService>>) -> Self {
+    pub fn new(paths: HashMap<String, Vec<Router>>, default_services: Vec<Router>) -> Self {
         Router::Router{ paths, default_services }
     }
 
     pub fn handle(&mut self, request: &HttpRequest) -> Result<Rc<HttpResponse>, Box<dyn Error>> {
         let path: Vec<_> = request.path().chars().collect();
+
+        if *request.method() == Method::Options {
+            let mut methods = Vec::new();
+            self.allowed_methods(&path, &mut methods);
+            if !methods.is_empty() {
+                return Ok(Rc::new(HttpResponseBuilder::new()
+                    .status(Status::NoContent)
+                    .header("Allow", allow_header(&methods))
+                    .body("")
+                    .build()));
+            }
+        }
+
         self.handle_internal(request, &path)
     }
 
-    fn handle_internal(&mut self, request: &HttpRequest, path: &[char]) -> Result<Rc<HttpResponse>, Box<dyn Error>> {
-        // TODO: Find a way to propagate the current path to the request handler
+    /// Collects the methods of every service registered for `path`
+    fn allowed_methods(&self, path: &[char], methods: &mut Vec<Method>) {
         match self {
             Self::Router{paths, default_services} => {
-                let (prefix, suffix) = compute_segments(&path);
-
-                let prefix: String = prefix.into_iter().collect();
-                match paths.get_mut(&prefix) {
-                    Some(next) => {
-                        for handler in next {
-                            match handler.handle_internal(request, suffix) {
-                                Ok(response) => return Ok(response),
-                                Err(_) => continue,
-                            }
-                        }
+                let (prefix, suffix) = compute_segments(path);
 
-                        for handler in default_services.iter_mut() {
-                            match handler.handle(request) {
-                                Ok(response) => return Ok(response),
-                                Err(_) => continue,
-                            }
-                        }
+                let prefix: String = prefix.iter().collect();
+                if let Some(next) = paths.get(&prefix) {
+                    for handler in next {
+                        handler.allowed_methods(suffix, methods);
+                    }
+                }
 
-                        Ok(Rc::new(HttpResponseBuilder::new().status(Status::NotFound).body("404 Not Found").build()))
+                for handler in default_services {
+                    handler.allowed_methods(path, methods);
+                }
+            }
+            Self::Service(method, _) => {
+                // A service without a method answers all of them
+                let served = match method {
+                    Some(method) => std::slice::from_ref(method),
+                    None => &Method::STANDARD[..],
+                };
+                for method in served {
+                    if !methods.contains(method) {
+                        methods.push(method.clone());
                     }
-                    None => {
-                        for handler in default_services.iter_mut() {
-                            match handler.handle(request) {
-                                Ok(response) => return Ok(response),
-                                Err(_) => continue,
-                            }
+                }
+            }
+        }
+    }
+
+    fn handle_internal(&mut self, request: &HttpRequest, path: &[char]) -> Result<Rc<HttpResponse>, Box<dyn Error>> {
+        // TODO: Find a way to propagate the current path to the request handler
+        match self {
+            Self::Router{paths, default_services} => {
+                let (prefix, suffix) = compute_segments(path);
+
+                let prefix: String = prefix.iter().collect();
+                if let Some(next) = paths.get_mut(&prefix) {
+                    for handler in next {
+                        match handler.handle_internal(request, suffix) {
+                            Ok(response) => return Ok(response),
+                            Err(_) => continue,
                         }
+                    }
+                }
 
-                        Ok(Rc::new(HttpResponseBuilder::new().status(Status::NotFound).body("404 Not Found").build()))
+                for handler in default_services.iter_mut() {
+                    match handler.handle_internal(request, path) {
+                        Ok(response) => return Ok(response),
+                        Err(_) => continue,
                     }
                 }
+
+                Ok(Rc::new(HttpResponseBuilder::new().status(Status::NotFound).body("404 Not Found").build()))
             }
             Self::Service(method, service) => {
                 match method {
-                    Some(method) => {
-                        if request.method() == method {
-                            return Ok(service.handle(request)?)
-                        } else {
-                            return Err(todo!("Create next fallback"));
-                        }
-                    }
-                    None => {
-                        return Ok(service.handle(request)?)
-                    }
+                    // HEAD is served by whatever serves GET
+                    Some(Method::Get) if *request.method() == Method::Head => service.handle(request),
+                    Some(method) if request.method() != method => Err(Box::new(MethodMismatch)),
+                    _ => service.handle(request),
                 }
             }
         }
     }
 }
-
@@ -1,9 +1,11 @@
 use std::{fmt::Debug, path::Path, rc::Rc, collections::HashMap};
 
+use http_server::static_files::StaticFiles;
+
 use super::{
     http_service::{ServeFileService, StaticResponseService},
     request::{HttpRequest, Method},
-    response::{HttpResponse, HttpResponseBuilder, Status},
+    response::HttpResponse,
     router::Router,
 };
 
@@ -14,6 +16,23 @@ pub struct RouterBuilder {
 #[derive(Default)]
 pub struct NoMethod;
 
+/// The method a route was restricted to, if any
+pub trait RouteMethod {
+    fn into_method(self) -> Option<Method>;
+}
+
+impl RouteMethod for NoMethod {
+    fn into_method(self) -> Option<Method> {
+        None
+    }
+}
+
+impl RouteMethod for Method {
+    fn into_method(self) -> Option<Method> {
+        Some(self)
+    }
+}
+
 pub struct RouterPathBuilder<'a, M> {
     builder: &'a mut RouterBuilder,
     path: PathSegment,
@@ -73,36 +92,23 @@ impl RouterBuilder {
     }
 
     pub fn build(self) -> Router {
-        let mut paths = HashMap::new();
-        let mut default_service = None;
+        let mut paths: HashMap<String, Vec<Router>> = HashMap::new();
+        let mut default_services = Vec::new();
 
         for route in self.routes.into_iter() {
             let RoutePathService {path, service} = route;
 
             match path {
                 PathSegment::Literal(path) => {
-                    paths.insert(path, service);
+                    paths.entry(path).or_default().push(service);
                 }
                 PathSegment::Wildcard => {
-                    default_service = Some(service);
+                    default_services.push(service);
                 }
             }
         }
-        
-        let default_service = if let Some(service) = default_service {
-            service
-        } else {
-            // Construct default service
-            let response = HttpResponseBuilder::new()
-                .status(Status::NotFound)
-                .body("Invalid path")
-                .build();
-            let response = Rc::new(response);
-            let service = StaticResponseService::new(response);
-            Router::Service(Box::new(service))
-        };
-
-        Router::new(paths, default_service)
+
+        Router::new(paths, default_services)
     }
 
     fn insert_handler(&mut self, service: RoutePathService) {
@@ -128,11 +134,11 @@ impl<'a> RouterPathBuilder<'a, NoMethod> {
     }
 }
 
-impl<'a, M> RouterPathBuilder<'a, M> {
+impl<'a, M: RouteMethod> RouterPathBuilder<'a, M> {
     pub fn static_res<R: Into<HttpResponse>>(self, response: R) {
         let service = StaticResponseService::new(Rc::new(response.into()));
         let service = Box::new(service);
-        let service = Router::Service(service);
+        let service = Router::Service(self.method.into_method(), service);
         let service = RoutePathService::new(self.path, service);
         self.builder.insert_handler(service);
     }
@@ -143,16 +149,26 @@ impl<'a, M> RouterPathBuilder<'a, M> {
     }
 
     pub fn file<PathType: AsRef<Path>>(self, path: PathType) {
-        let service = ServeFileService::new(path.as_ref().to_path_buf());
+        self.file_service(ServeFileService::new(path.as_ref().to_path_buf()));
+    }
+
+    /// Serves the files under `path` like `file`, listing directories that have no `index.html`
+    pub fn file_with_autoindex<PathType: AsRef<Path>>(self, path: PathType) {
+        let files = StaticFiles::new(path.as_ref().to_path_buf()).with_autoindex(true);
+        self.file_service(files.into());
+    }
+
+    /// Serves files with a service configured beyond its root directory
+    pub fn file_service(self, service: ServeFileService) {
         let service = Box::new(service);
-        let service = Router::Service(service);
+        let service = Router::Service(self.method.into_method(), service);
         let service = RoutePathService::new(self.path, service);
         self.builder.insert_handler(service);
     }
 
     pub fn handler(self, handler: fn(&HttpRequest) -> HttpResponse) {
         let service = Box::new(handler);
-        let service = Router::Service(service);
+        let service = Router::Service(self.method.into_method(), service);
         let service = RoutePathService::new(self.path, service);
         self.builder.insert_handler(service);
     }
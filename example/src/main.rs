@@ -31,13 +31,13 @@ fn main() {
     let mut router = RouterBuilder::new();
 
     router.route("api").defer(api);
-    router.route("*").method(Method::GET).file("./server");
+    router.route("*").method(Method::Get).file("./server");
     router.route("test")
         .defer({
             let mut router = RouterBuilder::new();
 
             router.route("in")
-                .method(Method::GET)
+                .method(Method::Get)
                 .static_res(HttpResponseBuilder::new().status(Status::Ok).body("in"));
             router.route("out")
                 .static_res(HttpResponseBuilder::new().status(Status::Ok).body("out"));
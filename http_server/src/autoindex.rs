@@ -0,0 +1,293 @@
+use std::{cmp::Ordering, collections::HashMap, fmt::Write as _, fs, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::date::format_http_date;
+use crate::header::HeaderMap;
+use crate::json::JsonValue;
+use crate::query::{percent_decode, percent_encode};
+use crate::resolve::{PathResolver, ResolveError};
+
+/// The column a listing is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "name" => Some(SortKey::Name),
+            "size" => Some(SortKey::Size),
+            "mtime" => Some(SortKey::Modified),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "mtime",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "asc" => Some(SortOrder::Ascending),
+            "desc" => Some(SortOrder::Descending),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "asc",
+            SortOrder::Descending => "desc",
+        }
+    }
+}
+
+/// How the entries of a listing are ordered, by ascending name unless asked otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sort {
+    pub key: SortKey,
+    pub order: SortOrder,
+}
+
+impl Sort {
+    /// Reads `sort=name|size|mtime` and `order=asc|desc` from a query, keeping the default for anything missing or unknown
+    pub fn from_query(query: &HashMap<String, Vec<String>>) -> Self {
+        let value = |key: &str| query.get(key).and_then(|values| values.first()).map(String::as_str);
+        Sort {
+            key: value("sort").and_then(SortKey::parse).unwrap_or_default(),
+            order: value("order").and_then(SortOrder::parse).unwrap_or_default(),
+        }
+    }
+}
+
+/// A file or directory in a listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    /// The length of files, zero for directories
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// The contents of a directory, rendered when it has no `index.html`.
+///
+/// Only entries the resolver would serve are listed, so hidden dotfiles and
+/// links the symlink policy refuses are left out.
+#[derive(Debug, Clone)]
+pub struct Listing {
+    /// The decoded request path of the directory, ending in `/`
+    path: String,
+    entries: Vec<Entry>,
+}
+
+impl Listing {
+    /// Lists the directory `request_path` resolves to, sorted by name
+    pub fn read<P: AsRef<Path>>(resolver: &PathResolver<P>, request_path: &str) -> Result<Self, ResolveError> {
+        let dir = resolver.resolve(request_path)?;
+
+        // The resolver accepted the path, so every segment decodes and none holds a separator
+        let mut path = String::from("/");
+        for segment in request_path.split('/').filter(|segment| !matches!(*segment, "" | ".")) {
+            path.push_str(&percent_decode(segment, false).map_err(|_| ResolveError::NotFound)?);
+            path.push('/');
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let Ok(entry) = entry else { continue };
+            // Names that are not UTF-8 could not be requested anyway
+            let Ok(name) = entry.file_name().into_string() else { continue };
+            let Ok(entry_path) = resolver.resolve(&format!("{}{}", encode_path(&path), percent_encode(&name))) else { continue };
+            let Ok(metadata) = fs::metadata(entry_path) else { continue };
+
+            entries.push(Entry {
+                name,
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified: metadata.modified().ok(),
+            });
+        }
+
+        let mut listing = Listing { path, entries };
+        listing.sort(Sort::default());
+        Ok(listing)
+    }
+
+    /// The decoded request path of the directory, ending in `/`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Orders the entries, always keeping directories ahead of files
+    pub fn sort(&mut self, sort: Sort) {
+        self.entries.sort_by(|a, b| {
+            let ordering = match sort.key {
+                SortKey::Name => Ordering::Equal,
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Modified => a.modified.cmp(&b.modified),
+            }
+            .then_with(|| a.name.cmp(&b.name));
+
+            let ordering = match sort.order {
+                SortOrder::Ascending => ordering,
+                SortOrder::Descending => ordering.reverse(),
+            };
+            b.is_dir.cmp(&a.is_dir).then(ordering)
+        });
+    }
+
+    /// Renders the listing as an HTML page, with column headers that sort by themselves.
+    ///
+    /// `sort` is the order the entries are in, which decides where the header links lead.
+    pub fn to_html(&self, sort: Sort) -> String {
+        let path = escape_html(&self.path);
+        let base = escape_html(&encode_path(&self.path));
+
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {path}</title>\n</head>\n<body>\n<h1>Index of {path}</h1>\n<table>\n<thead>\n<tr>"
+        );
+        for (key, title) in [(SortKey::Name, "Name"), (SortKey::Size, "Size"), (SortKey::Modified, "Modified")] {
+            // The current column flips its order, the others start ascending
+            let order = match sort {
+                Sort { key: current, order: SortOrder::Ascending } if current == key => SortOrder::Descending,
+                _ => SortOrder::Ascending,
+            };
+            let _ = write!(html, "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>", key.as_str(), order.as_str(), title);
+        }
+        html.push_str("</tr>\n</thead>\n<tbody>\n");
+
+        if let Some(parent) = self.path.trim_end_matches('/').rsplit_once('/').map(|(parent, _)| parent) {
+            let parent = escape_html(&encode_path(&format!("{}/", parent)));
+            let _ = writeln!(html, "<tr><td><a href=\"{}\">../</a></td><td>-</td><td>-</td></tr>", parent);
+        }
+
+        for entry in &self.entries {
+            let suffix = if entry.is_dir { "/" } else { "" };
+            let size = if entry.is_dir { "-".to_string() } else { entry.size.to_string() };
+            let modified = entry.modified.map(format_http_date).unwrap_or_else(|| "-".to_string());
+            let _ = writeln!(
+                html,
+                "<tr><td><a href=\"{}{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>",
+                base,
+                escape_html(&percent_encode(&entry.name)),
+                suffix,
+                escape_html(&entry.name),
+                suffix,
+                size,
+                modified,
+            );
+        }
+
+        html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// The listing as a JSON object, with modification times in seconds since the epoch
+    pub fn to_json(&self) -> JsonValue {
+        let entries: Vec<JsonValue> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let modified = entry
+                    .modified
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|modified| modified.as_secs());
+                JsonValue::from_iter([
+                    ("name", JsonValue::from(entry.name.as_str())),
+                    ("type", JsonValue::from(if entry.is_dir { "directory" } else { "file" })),
+                    ("size", JsonValue::from(entry.size)),
+                    ("modified", JsonValue::from(modified)),
+                ])
+            })
+            .collect();
+
+        JsonValue::from_iter([("path", JsonValue::from(self.path.as_str())), ("entries", JsonValue::from(entries))])
+    }
+}
+
+/// Encodes each segment of a decoded path, leaving the separators
+fn encode_path(path: &str) -> String {
+    path.split('/').map(percent_encode).collect::<Vec<_>>().join("/")
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The quality `accept` gives `media_type`, through the most specific range that matches it
+fn quality(accept: &str, media_type: &str) -> f32 {
+    let (kind, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+    let mut best: Option<(u8, f32)> = None;
+
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let range = params.next().unwrap_or("").trim().to_ascii_lowercase();
+        let specificity = if range == media_type {
+            2
+        } else if range == format!("{}/*", kind) {
+            1
+        } else if range == "*/*" {
+            0
+        } else {
+            continue;
+        };
+
+        let q = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+            .unwrap_or(1.0);
+        if best.is_none_or(|(best, _)| specificity > best) {
+            best = Some((specificity, q));
+        }
+    }
+
+    best.map(|(_, q)| q).unwrap_or(0.0)
+}
+
+/// Whether the client prefers a JSON listing over HTML, going by `Accept`
+pub fn wants_json(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get_all("Accept")
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let json = quality(&accept, "application/json");
+    // `*/*` alone is no reason to leave HTML
+    json > 0.0 && accept.to_ascii_lowercase().contains("application/json") && json >= quality(&accept, "text/html")
+}
@@ -0,0 +1,163 @@
+use std::{error::Error, fmt::{Debug, Display}, io::{self, BufRead, Read}};
+
+use crate::chunked::ChunkedDecoder;
+use crate::header::HeaderMap;
+
+/// Returned, wrapped in an `io::Error`, when a chunked body outgrows the server's body size limit
+#[derive(Debug)]
+pub struct BodyTooLarge(pub u64);
+
+impl Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The request body is larger than {} bytes", self.0)
+    }
+}
+
+impl Error for BodyTooLarge {}
+
+impl BodyTooLarge {
+    /// Checks whether reading a body failed because it was too large
+    pub fn is(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|err| err.is::<BodyTooLarge>())
+    }
+}
+
+/// Trailers of a body that cannot have any
+static NO_TRAILERS: HeaderMap = HeaderMap::new();
+
+/// Where a request body gets its bytes from
+pub(crate) trait BodySource: Read {
+    fn trailers(&self) -> Option<&HeaderMap>;
+}
+
+/// The body of an incoming request.
+///
+/// The body is not read up front: it borrows the connection and pulls bytes
+/// from the socket as the handler reads it, so large uploads can be processed
+/// incrementally. Whatever the handler leaves unread is discarded by the
+/// server before the next request on the connection.
+pub struct RequestBody<'a> {
+    reader: Option<&'a mut dyn BodySource>,
+    content_length: Option<u64>,
+}
+
+impl <'a> RequestBody<'a> {
+    pub fn empty() -> Self {
+        RequestBody { reader: None, content_length: Some(0) }
+    }
+
+    pub(crate) fn new(reader: &'a mut dyn BodySource, content_length: Option<u64>) -> Self {
+        RequestBody { reader: Some(reader), content_length }
+    }
+
+    /// The declared length of the body, if the client sent one
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Trailer fields sent after a chunked body, available once it has been read to the end
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        match &self.reader {
+            Some(reader) => reader.trailers(),
+            None => Some(&NO_TRAILERS),
+        }
+    }
+
+    /// Reads the rest of the body into memory.
+    ///
+    /// Not called `bytes`, as `Read::bytes` would be picked over it whenever `Read` is in scope
+    pub fn read_to_vec(&mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl Read for RequestBody<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.reader {
+            Some(reader) => reader.read(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Debug for RequestBody<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestBody")
+            .field("content_length", &self.content_length)
+            .finish_non_exhaustive()
+    }
+}
+
+enum Framing {
+    Length(u64),
+    Chunked { decoder: ChunkedDecoder, read: u64, limit: u64 },
+}
+
+/// Reads request bodies off a connection, never past the end of the current one.
+pub(crate) struct BodyReader<R: BufRead> {
+    inner: R,
+    framing: Framing,
+}
+
+impl <R: BufRead> BodyReader<R> {
+    pub fn new(inner: R) -> Self {
+        BodyReader { inner, framing: Framing::Length(0) }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Starts reading a body of `length` bytes
+    pub fn start(&mut self, length: u64) {
+        self.framing = Framing::Length(length);
+    }
+
+    /// Starts reading a body sent with `Transfer-Encoding: chunked`, failing once it exceeds `limit` bytes
+    pub fn start_chunked(&mut self, limit: u64) {
+        self.framing = Framing::Chunked { decoder: ChunkedDecoder::new(), read: 0, limit };
+    }
+
+    /// Skips whatever the handler did not read of the current body
+    pub fn discard(&mut self) -> io::Result<()> {
+        io::copy(self, &mut io::sink())?;
+        Ok(())
+    }
+}
+
+impl <R: BufRead> Read for BodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.framing {
+            Framing::Length(0) => Ok(0),
+            Framing::Length(remaining) => {
+                let max = buf.len().min((*remaining).try_into().unwrap_or(usize::MAX));
+                let read = self.inner.read(&mut buf[..max])?;
+                if read == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed before the end of the request body"));
+                }
+
+                *remaining -= read as u64;
+                Ok(read)
+            }
+            Framing::Chunked { decoder, read, limit } => {
+                let chunk = decoder.read(&mut self.inner, buf)?;
+                *read += chunk as u64;
+                if *read > *limit {
+                    return Err(io::Error::other(BodyTooLarge(*limit)));
+                }
+                Ok(chunk)
+            }
+        }
+    }
+}
+
+impl <R: BufRead> BodySource for BodyReader<R> {
+    fn trailers(&self) -> Option<&HeaderMap> {
+        match &self.framing {
+            Framing::Length(_) => Some(&NO_TRAILERS),
+            Framing::Chunked { decoder, .. } => decoder.trailers(),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::io::{self, BufRead, Read, Write};
+
+use crate::header::HeaderMap;
+
+/// Longest chunk size or trailer line accepted, extensions included
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+/// Most trailer fields accepted after the last chunk
+const MAX_TRAILERS: usize = 100;
+
+#[derive(Debug)]
+enum State {
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body into the bytes it carries.
+///
+/// Chunk extensions are skipped and trailer fields are collected so they can
+/// be inspected once the body has been read to the end.
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: State,
+    trailers: HeaderMap,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_line<R: BufRead>(inner: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    inner.take(MAX_LINE_LENGTH).read_line(&mut line)?;
+    match line.strip_suffix('\n') {
+        Some(line) => Ok(line.strip_suffix('\r').unwrap_or(line).to_string()),
+        None if line.len() as u64 == MAX_LINE_LENGTH => Err(invalid_data("Chunk line too long")),
+        None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed inside a chunked body")),
+    }
+}
+
+fn parse_chunk_size(line: &str) -> io::Result<u64> {
+    // Extensions follow the size after a ';' and carry nothing we act on
+    let size = line.split(';').next().unwrap_or("").trim();
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid_data("Invalid chunk size"));
+    }
+    u64::from_str_radix(size, 16).map_err(|_| invalid_data("Chunk size too large"))
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        ChunkedDecoder { state: State::Size, trailers: HeaderMap::new() }
+    }
+
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        match self.state {
+            State::Done => Some(&self.trailers),
+            _ => None,
+        }
+    }
+
+    pub fn read<R: BufRead>(&mut self, inner: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.state {
+                State::Size => {
+                    let size = parse_chunk_size(&read_line(inner)?)?;
+                    self.state = if size == 0 { State::Trailers } else { State::Data(size) };
+                }
+                State::Data(remaining) => {
+                    if buf.is_empty() {
+                        return Ok(0);
+                    }
+
+                    let max = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+                    let read = inner.read(&mut buf[..max])?;
+                    if read == 0 {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed inside a chunk"));
+                    }
+
+                    let remaining = remaining - read as u64;
+                    self.state = if remaining == 0 { State::DataEnd } else { State::Data(remaining) };
+                    return Ok(read);
+                }
+                State::DataEnd => {
+                    if !read_line(inner)?.is_empty() {
+                        return Err(invalid_data("Chunk data longer than its declared size"));
+                    }
+                    self.state = State::Size;
+                }
+                State::Trailers => {
+                    let line = read_line(inner)?;
+                    if line.is_empty() {
+                        self.state = State::Done;
+                        continue;
+                    }
+
+                    if self.trailers.len() == MAX_TRAILERS {
+                        return Err(invalid_data("Too many trailer fields"));
+                    }
+
+                    let (key, value) = line.split_once(':').ok_or_else(|| invalid_data("Invalid trailer field"))?;
+                    self.trailers.append(key.trim(), value.trim());
+                }
+                State::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Frames everything written through it as `Transfer-Encoding: chunked`.
+///
+/// Every non-empty `write` becomes one chunk; `finish` writes the last chunk
+/// and the trailer fields.
+pub(crate) struct ChunkedEncoder<W: Write> {
+    inner: W,
+}
+
+impl <W: Write> ChunkedEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        ChunkedEncoder { inner }
+    }
+
+    pub fn finish(mut self, trailers: &HeaderMap) -> io::Result<W> {
+        trailers.validate()?;
+        self.inner.write_all(b"0\r\n")?;
+        for (key, value) in trailers.iter() {
+            self.inner.write_all(format!("{}: ", key).as_bytes())?;
+            self.inner.write_all(value.as_bytes())?;
+            self.inner.write_all(b"\r\n")?;
+        }
+        self.inner.write_all(b"\r\n")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl <W: Write> Write for ChunkedEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // An empty chunk would end the body early
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.inner.write_all(format!("{:X}\r\n", buf.len()).as_bytes())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::body::{BodyReader, BodyTooLarge};
+
+    use super::*;
+
+    /// Decodes all of `input`, returning the body and its trailer fields
+    fn decode(input: &[u8]) -> io::Result<(Vec<u8>, HeaderMap)> {
+        let mut inner = input;
+        let mut decoder = ChunkedDecoder::new();
+        let mut body = Vec::new();
+        let mut buf = [0; 4];
+        loop {
+            let read = decoder.read(&mut inner, &mut buf)?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&buf[..read]);
+        }
+        Ok((body, decoder.trailers().cloned().unwrap_or_default()))
+    }
+
+    fn error_kind(input: &[u8]) -> io::ErrorKind {
+        decode(input).unwrap_err().kind()
+    }
+
+    #[test]
+    fn decodes_chunks() {
+        let (body, trailers) = decode(b"5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n").unwrap();
+        assert_eq!(body, b"hello, world");
+        assert!(trailers.is_empty());
+
+        // Bare LF line endings are tolerated
+        let (body, _) = decode(b"a\nHello, you\n0\n\n").unwrap();
+        assert_eq!(body, b"Hello, you");
+    }
+
+    #[test]
+    fn skips_extensions() {
+        let (body, _) = decode(b"5;name=value;flag\r\nhello\r\n0;last\r\n\r\n").unwrap();
+        assert_eq!(body, b"hello");
+    }
+
+    #[test]
+    fn collects_trailers() {
+        let (body, trailers) = decode(b"2\r\nhi\r\n0\r\nX-Checksum: abc\r\nX-Note:  a b \r\n\r\n").unwrap();
+        assert_eq!(body, b"hi");
+        assert_eq!(trailers.get_str("x-checksum"), Some("abc"));
+        assert_eq!(trailers.get_str("X-Note"), Some("a b"));
+        assert_eq!(error_kind(b"0\r\nno colon\r\n\r\n"), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn limits_trailers() {
+        let mut input = b"0\r\n".to_vec();
+        for i in 0..MAX_TRAILERS {
+            input.extend_from_slice(format!("X-{}: {}\r\n", i, i).as_bytes());
+        }
+        let mut accepted = input.clone();
+        accepted.extend_from_slice(b"\r\n");
+        assert_eq!(decode(&accepted).unwrap().1.len(), MAX_TRAILERS);
+
+        input.extend_from_slice(b"X-Extra: 1\r\n\r\n");
+        assert_eq!(error_kind(&input), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn limits_line_length() {
+        // The longest line accepted fills the limit with its LF
+        let padding = "x".repeat(MAX_LINE_LENGTH as usize - 3);
+        let (body, _) = decode(format!("1;{}\n.\r\n0\r\n\r\n", padding).as_bytes()).unwrap();
+        assert_eq!(body, b".");
+
+        let padding = "x".repeat(MAX_LINE_LENGTH as usize);
+        assert_eq!(error_kind(format!("1;{}\r\n.\r\n0\r\n\r\n", padding).as_bytes()), io::ErrorKind::InvalidData);
+        assert_eq!(error_kind(format!("0\r\nX-Long: {}\r\n\r\n", padding).as_bytes()), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for size in ["", "zz", "-1", "+1", "0x10", "1 2", "10000000000000000"] {
+            assert_eq!(error_kind(format!("{}\r\n", size).as_bytes()), io::ErrorKind::InvalidData, "{:?}", size);
+        }
+        // The largest size still parses, the body just ends long before it
+        assert_eq!(error_kind(b"FFFFFFFFFFFFFFFF\r\nabc"), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_data_past_declared_size() {
+        assert_eq!(error_kind(b"3\r\nhello\r\n0\r\n\r\n"), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_bodies() {
+        for input in [&b"5\r\nhel"[..], b"5\r\nhello", b"5\r\nhello\r\n", b"5", b"0\r\nX-Checksum: abc\r\n"] {
+            assert_eq!(error_kind(input), io::ErrorKind::UnexpectedEof, "{:?}", String::from_utf8_lossy(input));
+        }
+    }
+
+    #[test]
+    fn body_reader_limits_chunked_bodies() {
+        let input = b"5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n";
+
+        let mut reader = BodyReader::new(&input[..]);
+        reader.start_chunked(10);
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        assert_eq!(body, b"helloworld");
+
+        let mut reader = BodyReader::new(&input[..]);
+        reader.start_chunked(9);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(BodyTooLarge::is(&err));
+    }
+}
@@ -0,0 +1,59 @@
+use std::{io, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Number of worker threads handling connections
+    pub worker_threads: usize,
+    /// Number of accepted connections that may wait for a free worker
+    pub queue_depth: usize,
+    /// Number of requests served on one connection before it is closed
+    pub max_requests_per_connection: usize,
+    /// How long an open connection may sit idle waiting for its next request
+    pub keep_alive_timeout: Duration,
+    /// How long a client has to send the request line and headers once it starts a request
+    pub header_read_timeout: Duration,
+    /// How long reading the request body may wait for more bytes
+    pub body_read_timeout: Duration,
+    /// How long writing the response may wait for the client to accept more bytes
+    pub write_timeout: Duration,
+    /// Longest request line accepted, not counting its CRLF, answered with `414 URI Too Long` when exceeded
+    pub max_request_line_length: usize,
+    /// Most header fields accepted in one request
+    pub max_header_count: usize,
+    /// Largest header section accepted, in bytes, not counting the CRLF ending each field
+    pub max_header_size: usize,
+    /// Largest request body accepted, unless the request handler overrides it
+    pub max_body_size: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            // Workers block on socket I/O, so this is deliberately not tied to the core count
+            worker_threads: 16,
+            queue_depth: 64,
+            max_requests_per_connection: 100,
+            keep_alive_timeout: Duration::from_secs(5),
+            header_read_timeout: Duration::from_secs(10),
+            body_read_timeout: Duration::from_secs(30),
+            write_timeout: Duration::from_secs(30),
+            max_request_line_length: 8 * 1024,
+            max_header_count: 100,
+            max_header_size: 16 * 1024,
+            max_body_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Checks the settings a server cannot run with, before anything is started
+    pub(crate) fn validate(&self) -> io::Result<()> {
+        if self.worker_threads == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "worker_threads must be at least 1"));
+        }
+        if self.max_requests_per_connection == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "max_requests_per_connection must be at least 1"));
+        }
+        Ok(())
+    }
+}
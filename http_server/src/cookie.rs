@@ -0,0 +1,252 @@
+use std::{error::Error, fmt::Display, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::date::format_http_date;
+use crate::header::HeaderMap;
+use crate::request::is_token;
+
+/// The cookies a client sent, in the order they appeared in its `Cookie` fields
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar {
+    cookies: Vec<(String, String)>,
+}
+
+impl CookieJar {
+    /// Parses every `Cookie` field in `headers`, skipping pairs that are not `name=value`
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut cookies = Vec::new();
+        for field in headers.get_all("Cookie") {
+            let field = String::from_utf8_lossy(field.as_bytes());
+            for pair in field.split(';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let name = name.trim();
+                if name.is_empty() {
+                    continue;
+                }
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+                cookies.push((name.to_string(), value.to_string()));
+            }
+        }
+        CookieJar { cookies }
+    }
+
+    /// The value of the first cookie called `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.cookies.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl Display for SameSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// Bytes allowed in a cookie value by RFC 6265
+fn is_cookie_value(value: &str) -> bool {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    value.bytes().all(|b| matches!(b, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E))
+}
+
+/// Bytes allowed in the value of a `Path` or `Domain` attribute
+fn is_attribute_value(value: &str) -> bool {
+    value.bytes().all(|b| !b.is_ascii_control() && b != b';' && b.is_ascii())
+}
+
+/// A cookie name or attribute that cannot be written into a `Set-Cookie` field, with the offending input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieError {
+    /// The name is not a token
+    InvalidName(String),
+    /// The value holds bytes a cookie value cannot, like spaces, commas, semicolons or control characters
+    InvalidValue(String),
+    /// The path holds a semicolon, control characters or non-ASCII bytes
+    InvalidPath(String),
+    /// The domain holds a semicolon, control characters or non-ASCII bytes
+    InvalidDomain(String),
+}
+
+impl Display for CookieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CookieError::InvalidName(name) => write!(f, "Invalid cookie name {:?}", name),
+            CookieError::InvalidValue(value) => write!(f, "Invalid cookie value {:?}", value),
+            CookieError::InvalidPath(path) => write!(f, "Invalid cookie path {:?}", path),
+            CookieError::InvalidDomain(domain) => write!(f, "Invalid cookie domain {:?}", domain),
+        }
+    }
+}
+
+impl Error for CookieError {}
+
+/// A cookie to send with a `Set-Cookie` field, along with its attributes.
+///
+/// Each cookie goes in its own field, as `Set-Cookie` cannot be folded into a
+/// comma separated list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<u64>,
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl SetCookie {
+    /// Fails if `name` is not a token or `value` holds bytes a cookie value cannot,
+    /// so cookies built from request data cannot inject attributes of their own
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Result<Self, CookieError> {
+        let name = name.into();
+        let value = value.into();
+        if !is_token(&name) {
+            return Err(CookieError::InvalidName(name));
+        }
+        if !is_cookie_value(&value) {
+            return Err(CookieError::InvalidValue(value));
+        }
+
+        Ok(SetCookie {
+            name,
+            value,
+            path: None,
+            domain: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        })
+    }
+
+    /// A cookie that makes the client forget the cookie called `name`
+    pub fn removal<N: Into<String>>(name: N) -> Result<Self, CookieError> {
+        Ok(SetCookie::new(name, "")?.max_age(Duration::ZERO).expires(UNIX_EPOCH))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Fails if `path` holds a semicolon, control characters or non-ASCII bytes
+    pub fn path<S: Into<String>>(mut self, path: S) -> Result<Self, CookieError> {
+        let path = path.into();
+        if !is_attribute_value(&path) {
+            return Err(CookieError::InvalidPath(path));
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Fails if `domain` holds a semicolon, control characters or non-ASCII bytes
+    pub fn domain<S: Into<String>>(mut self, domain: S) -> Result<Self, CookieError> {
+        let domain = domain.into();
+        if !is_attribute_value(&domain) {
+            return Err(CookieError::InvalidDomain(domain));
+        }
+        self.domain = Some(domain);
+        Ok(self)
+    }
+
+    /// How long the cookie lives, to the second
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age.as_secs());
+        self
+    }
+
+    pub fn expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Only send the cookie back over HTTPS
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    /// Hide the cookie from scripts
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+}
+
+impl Display for SetCookie {
+    /// Writes the value of the `Set-Cookie` field
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", format_http_date(expires))?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+        Ok(())
+    }
+}
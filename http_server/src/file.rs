@@ -0,0 +1,72 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::Path,
+};
+
+use crate::header::HeaderMap;
+use crate::response::HttpBody;
+
+/// A response body streamed from a file, in constant memory however large it is.
+///
+/// The length is taken from the file's metadata when the body is created.
+/// Sending to a socket goes through `io::copy`, which on Linux hands the file
+/// over with `sendfile(2)` without passing through user space; elsewhere, and
+/// for writers other than sockets, it is copied through a buffer.
+#[derive(Debug)]
+pub struct FileBody {
+    file: File,
+    length: u64,
+}
+
+impl FileBody {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        FileBody::new(File::open(path)?)
+    }
+
+    /// Sends `file` from its start, failing if it is not a regular file
+    pub fn new(file: File) -> io::Result<Self> {
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not a regular file"));
+        }
+        Ok(FileBody { file, length: metadata.len() })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Copies the file to `writer`, letting `io::copy` pick the fastest way for concrete writers
+    fn copy_to<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        let copied = io::copy(&mut (&self.file).take(self.length), writer)?;
+        // Content-Length promised every byte, a short body must not look complete
+        if copied < self.length {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The file shrank while it was being sent"));
+        }
+        Ok(())
+    }
+}
+
+impl HttpBody for FileBody {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("Content-Length", self.length.to_string());
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        self.copy_to(writer)
+    }
+
+    fn send_body(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        self.copy_to(stream)
+    }
+}
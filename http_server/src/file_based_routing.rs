@@ -1,32 +1,47 @@
-use std::{path::Path, collections::HashMap, fs};
+use std::path::Path;
 
-use crate::{request::HttpRequest, query::QueryPath, response::{HttpBody, HttpResponse, HttpVersion}};
+use crate::{header::HeaderMap, request::{self, HttpRequest, Method}, query::QueryPath, response::{HttpBody, HttpResponse, HttpVersion}, static_files::StaticFiles};
 use crate::response::*;
+use crate::router::RequestHandler;
+
+/// A plain text response, for the errors the router answers with
+fn text_response(status: Status, mut headers: HeaderMap, body: &str) -> HttpResponse<Box<dyn HttpBody>> {
+    headers.insert("Content-Type", "text/plain; charset=utf-8");
+    HttpResponse::new(HttpVersion::Http1_1, status, headers, Box::new(body.to_string()))
+}
 
 pub struct Router<P: AsRef<Path>> {
-    path: P
+    files: StaticFiles<P>,
 }
 
 impl <P: AsRef<Path>> Router<P> {
     pub fn new(path: P) -> Self {
-        Router { path }
+        Router { files: StaticFiles::new(path) }
     }
 
-    pub fn handle_request(&mut self, request: HttpRequest<QueryPath<String>>) -> HttpResponse<Box<dyn HttpBody>> {
-        println!("{:?}", &request.path.path);
-        let mut path = self.path.as_ref().join(request.path.path.strip_prefix("/").unwrap());
-        if path.extension().is_none() {
-            path = path.join("index.html");
-        }
-        println!("{:?}", &path);
-
-        if path.exists() {
-            let file_contents = fs::read_to_string(path).unwrap();
-            HttpResponse::new(HttpVersion::Http1_1, Status::Ok, HashMap::new(), Box::new(file_contents))
-        } else {
-            let body = "File not found!".to_string();
-            HttpResponse::new(HttpVersion::Http1_1, Status::NotFound, HashMap::new(), Box::new(body))
+    pub fn handle_request(&self, request: HttpRequest<'_, QueryPath<String>>) -> HttpResponse<Box<dyn HttpBody>> {
+        if request.method != Method::Get {
+            let headers = HeaderMap::from([("Allow", request::allow_header(&[Method::Get]))]);
+            return text_response(Status::MethodNotAllowed, headers, "Method not allowed!");
         }
+
+        self.files.respond(&request.path.path, &request.path.query, &request.headers).into()
     }
 }
 
+/// Serves files set up beyond the defaults, like with autoindex on
+impl <P: AsRef<Path>> From<StaticFiles<P>> for Router<P> {
+    fn from(files: StaticFiles<P>) -> Self {
+        Router { files }
+    }
+}
+
+impl <P: AsRef<Path>> RequestHandler<QueryPath<String>, Box<dyn HttpBody>> for Router<P> {
+    fn handle(&self, request: HttpRequest<'_, QueryPath<String>>) -> HttpResponse<Box<dyn HttpBody>> {
+        self.handle_request(request)
+    }
+
+    fn allowed_methods(&self, _request: &HttpRequest<'_, QueryPath<String>>) -> Option<Vec<Method>> {
+        Some(vec![Method::Get])
+    }
+}
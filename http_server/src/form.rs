@@ -0,0 +1,94 @@
+use std::{error::Error, fmt::Display, io};
+
+use crate::body::BodyTooLarge;
+use crate::from_query::{FromQuery, QueryError};
+use crate::header::HeaderMap;
+use crate::media_type;
+use crate::path::Path;
+use crate::query::{parse_urlencoded, DecodeError};
+use crate::request::HttpRequest;
+use crate::response::{ErrorResponse, Status};
+use crate::server::is_timeout;
+
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+#[derive(Debug)]
+pub enum FormError {
+    /// The request is not a form, with the content type it declared
+    UnsupportedMediaType(Option<String>),
+    /// Reading the body failed
+    Io(io::Error),
+    /// The body is not valid `application/x-www-form-urlencoded` data
+    Decode(DecodeError),
+    /// A field could not be converted
+    Field(QueryError),
+}
+
+impl ErrorResponse for FormError {
+    fn status(&self) -> Status {
+        match self {
+            FormError::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            FormError::Io(err) if BodyTooLarge::is(err) => Status::ContentTooLarge,
+            FormError::Io(err) if is_timeout(err) => Status::RequestTimeout,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+impl Display for FormError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormError::UnsupportedMediaType(Some(content_type)) => write!(f, "Expected {}, found {:?}", FORM_CONTENT_TYPE, content_type),
+            FormError::UnsupportedMediaType(None) => write!(f, "Expected {}, found no Content-Type", FORM_CONTENT_TYPE),
+            FormError::Io(err) => write!(f, "Failed to read the form: {}", err),
+            FormError::Decode(err) => write!(f, "Invalid form: {}", err),
+            FormError::Field(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for FormError {}
+
+impl From<io::Error> for FormError {
+    fn from(err: io::Error) -> Self {
+        FormError::Io(err)
+    }
+}
+
+impl From<DecodeError> for FormError {
+    fn from(err: DecodeError) -> Self {
+        FormError::Decode(err)
+    }
+}
+
+impl From<QueryError> for FormError {
+    fn from(err: QueryError) -> Self {
+        FormError::Field(err)
+    }
+}
+
+/// Checks that `headers` declare an `application/x-www-form-urlencoded` body
+pub fn check_content_type(headers: &HeaderMap) -> Result<(), FormError> {
+    media_type::check_content_type(headers, |essence| essence == FORM_CONTENT_TYPE)
+        .map(drop)
+        .map_err(FormError::UnsupportedMediaType)
+}
+
+/// Decodes a form body sent with `headers` into a `T`, which can be the
+/// multi-valued map itself or a struct declared with [`from_query!`](crate::from_query)
+pub fn parse_form<T: FromQuery>(headers: &HeaderMap, body: &[u8]) -> Result<T, FormError> {
+    check_content_type(headers)?;
+    let body = std::str::from_utf8(body).map_err(|_| DecodeError::InvalidUtf8)?;
+    Ok(T::from_query(&parse_urlencoded(body)?)?)
+}
+
+impl <P: Path> HttpRequest<'_, P> {
+    /// Reads the rest of the body as a form.
+    ///
+    /// The content type is checked before any of the body is read.
+    pub fn form<T: FromQuery>(&mut self) -> Result<T, FormError> {
+        check_content_type(&self.headers)?;
+        let body = self.body.read_to_vec()?;
+        parse_form(&self.headers, &body)
+    }
+}
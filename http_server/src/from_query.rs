@@ -0,0 +1,188 @@
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use crate::path::Path;
+use crate::query::QueryPath;
+use crate::response::{ErrorResponse, Status};
+
+/// A query parameter that could not be converted into the field it fills
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub field: String,
+    pub message: String,
+}
+
+impl QueryError {
+    pub fn new<F: Into<String>, M: Into<String>>(field: F, message: M) -> Self {
+        QueryError { field: field.into(), message: message.into() }
+    }
+
+    pub fn missing<F: Into<String>>(field: F) -> Self {
+        QueryError::new(field, "missing value")
+    }
+}
+
+/// Answers with a `400 Bad Request` naming the field at fault
+impl ErrorResponse for QueryError {
+    fn status(&self) -> Status {
+        Status::BadRequest
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid parameter {:?}: {}", self.field, self.message)
+    }
+}
+
+impl Error for QueryError {}
+
+/// A type that can be parsed from a single query value
+pub trait FromQueryValue: Sized {
+    fn from_value(value: &str) -> Result<Self, String>;
+}
+
+impl FromQueryValue for String {
+    fn from_value(value: &str) -> Result<Self, String> {
+        Ok(value.to_string())
+    }
+}
+
+impl FromQueryValue for bool {
+    fn from_value(value: &str) -> Result<Self, String> {
+        match value {
+            "true" | "1" | "on" | "yes" => Ok(true),
+            "false" | "0" | "off" | "no" => Ok(false),
+            _ => Err(format!("expected a boolean, found {:?}", value)),
+        }
+    }
+}
+
+macro_rules! from_str_values {
+    ($($ty:ty),*) => {
+        $(
+            impl FromQueryValue for $ty {
+                fn from_value(value: &str) -> Result<Self, String> {
+                    value.parse().map_err(|err| format!("{} ({:?})", err, value))
+                }
+            }
+        )*
+    };
+}
+
+from_str_values!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, char);
+
+/// A struct field filled from every value given for its key.
+///
+/// Plain values take the first one and fail when there is none, `Option`
+/// is `None` when the key is absent, and `Vec` collects all of them.
+pub trait FromQueryField: Sized {
+    fn from_values(field: &str, values: &[String]) -> Result<Self, QueryError>;
+}
+
+impl <T: FromQueryValue> FromQueryField for T {
+    fn from_values(field: &str, values: &[String]) -> Result<Self, QueryError> {
+        let value = values.first().ok_or_else(|| QueryError::missing(field))?;
+        T::from_value(value).map_err(|message| QueryError::new(field, message))
+    }
+}
+
+impl <T: FromQueryValue> FromQueryField for Option<T> {
+    fn from_values(field: &str, values: &[String]) -> Result<Self, QueryError> {
+        match values.is_empty() {
+            true => Ok(None),
+            false => T::from_values(field, values).map(Some),
+        }
+    }
+}
+
+impl <T: FromQueryValue> FromQueryField for Vec<T> {
+    fn from_values(field: &str, values: &[String]) -> Result<Self, QueryError> {
+        values
+            .iter()
+            .map(|value| T::from_value(value).map_err(|message| QueryError::new(field, message)))
+            .collect()
+    }
+}
+
+/// A type built from the key-value pairs of a query string or form.
+///
+/// Usually implemented through the [`from_query!`](crate::from_query) macro.
+pub trait FromQuery: Sized {
+    fn from_query(query: &HashMap<String, Vec<String>>) -> Result<Self, QueryError>;
+}
+
+impl FromQuery for HashMap<String, Vec<String>> {
+    fn from_query(query: &HashMap<String, Vec<String>>) -> Result<Self, QueryError> {
+        Ok(query.clone())
+    }
+}
+
+impl <P: Path> QueryPath<P> {
+    /// Builds a `T` out of the query parameters
+    pub fn parse_query<T: FromQuery>(&self) -> Result<T, QueryError> {
+        T::from_query(&self.query)
+    }
+}
+
+/// Declares a struct along with a [`FromQuery`] implementation filling each
+/// field from the query parameter of the same name.
+///
+/// A field can be given a default, used when its key is absent:
+///
+/// ```
+/// http_server::from_query! {
+///     #[derive(Debug)]
+///     pub struct Search {
+///         pub q: String,
+///         pub page: u32 = 1,
+///         pub exact: Option<bool>,
+///         pub tag: Vec<String>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! from_query {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty $(= $default:expr)?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::from_query::FromQuery for $name {
+            fn from_query(
+                query: &::std::collections::HashMap<::std::string::String, ::std::vec::Vec<::std::string::String>>,
+            ) -> ::std::result::Result<Self, $crate::from_query::QueryError> {
+                ::std::result::Result::Ok($name {
+                    $(
+                        $field: {
+                            let values = query.get(stringify!($field)).map(::std::vec::Vec::as_slice).unwrap_or_default();
+                            $crate::from_query!(@field stringify!($field), values, $ty $(, $default)?)
+                        },
+                    )*
+                })
+            }
+        }
+    };
+
+    (@field $key:expr, $values:expr, $ty:ty) => {
+        <$ty as $crate::from_query::FromQueryField>::from_values($key, $values)?
+    };
+
+    (@field $key:expr, $values:expr, $ty:ty, $default:expr) => {
+        match $values.is_empty() {
+            true => $default,
+            false => <$ty as $crate::from_query::FromQueryField>::from_values($key, $values)?,
+        }
+    };
+}
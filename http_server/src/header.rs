@@ -0,0 +1,203 @@
+use std::{fmt::Debug, io, str::Utf8Error};
+
+use crate::request::is_token;
+
+/// The value of a header field, kept as the raw bytes sent on the wire
+#[derive(Clone, PartialEq, Eq)]
+pub struct HeaderValue(Vec<u8>);
+
+impl HeaderValue {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn to_str(&self) -> Result<&str, Utf8Error> {
+        std::str::from_utf8(&self.0)
+    }
+
+    /// Whether the value can be written out as is, holding no control characters besides tabs (RFC 9110 section 5.5)
+    pub fn is_valid(&self) -> bool {
+        self.0.iter().all(|&b| b == b'\t' || !b.is_ascii_control())
+    }
+}
+
+impl Debug for HeaderValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_str() {
+            Ok(value) => write!(f, "{:?}", value),
+            Err(_) => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+impl From<String> for HeaderValue {
+    fn from(value: String) -> Self {
+        HeaderValue(value.into_bytes())
+    }
+}
+
+impl From<&str> for HeaderValue {
+    fn from(value: &str) -> Self {
+        HeaderValue(value.as_bytes().to_vec())
+    }
+}
+
+impl From<&String> for HeaderValue {
+    fn from(value: &String) -> Self {
+        HeaderValue(value.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for HeaderValue {
+    fn from(value: Vec<u8>) -> Self {
+        HeaderValue(value)
+    }
+}
+
+impl From<&[u8]> for HeaderValue {
+    fn from(value: &[u8]) -> Self {
+        HeaderValue(value.to_vec())
+    }
+}
+
+impl PartialEq<str> for HeaderValue {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for HeaderValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+/// Header fields of a request or response.
+///
+/// Names are compared case-insensitively but written out as they were
+/// inserted. Fields keep their insertion order and a name can carry several
+/// values, as with `Set-Cookie`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, HeaderValue)>,
+}
+
+impl HeaderMap {
+    pub const fn new() -> Self {
+        HeaderMap { entries: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains<S: AsRef<str>>(&self, name: S) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The first value of the field `name`
+    pub fn get<S: AsRef<str>>(&self, name: S) -> Option<&HeaderValue> {
+        self.get_all(name).next()
+    }
+
+    /// The first value of the field `name`, if it is valid UTF-8
+    pub fn get_str<S: AsRef<str>>(&self, name: S) -> Option<&str> {
+        self.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// Every value of the field `name`, in the order they were added
+    pub fn get_all<S: AsRef<str>>(&self, name: S) -> impl Iterator<Item = &HeaderValue> {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the field `name` to `value`, replacing any values it had
+    pub fn insert<K: Into<String>, V: Into<HeaderValue>>(&mut self, name: K, value: V) {
+        let name = name.into();
+        let value = value.into();
+
+        match self.entries.iter().position(|(key, _)| key.eq_ignore_ascii_case(&name)) {
+            Some(index) => {
+                // Later values of the field go, the first one is replaced where it stands
+                let mut position = 0;
+                self.entries.retain(|(key, _)| {
+                    let keep = position <= index || !key.eq_ignore_ascii_case(&name);
+                    position += 1;
+                    keep
+                });
+                self.entries[index] = (name, value);
+            }
+            None => self.entries.push((name, value)),
+        }
+    }
+
+    /// Adds another value to the field `name`, keeping the ones it already had
+    pub fn append<K: Into<String>, V: Into<HeaderValue>>(&mut self, name: K, value: V) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Removes every value of the field `name`, returning the first one
+    pub fn remove<S: AsRef<str>>(&mut self, name: S) -> Option<HeaderValue> {
+        let name = name.as_ref();
+        let mut removed = None;
+        self.entries.retain(|(key, value)| {
+            if !key.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(value.clone());
+            }
+            false
+        });
+        removed
+    }
+
+    /// Every field in insertion order, once per value
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &HeaderValue)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Checks every field can be written out without changing how the message is framed.
+    ///
+    /// Names must be tokens and values must not hold line breaks or other
+    /// control characters, which would let them start fields of their own.
+    pub fn validate(&self) -> io::Result<()> {
+        for (key, value) in self.iter() {
+            if !is_token(key) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid header name {:?}", key)));
+            }
+            if !value.is_valid() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid value for header {:?}", key)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for HeaderMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl <K: Into<String>, V: Into<HeaderValue>> FromIterator<(K, V)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = HeaderMap::new();
+        for (key, value) in iter {
+            headers.append(key, value);
+        }
+        headers
+    }
+}
+
+impl <K: Into<String>, V: Into<HeaderValue>, const N: usize> From<[(K, V); N]> for HeaderMap {
+    fn from(entries: [(K, V); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
@@ -0,0 +1,657 @@
+use std::{collections::HashMap, error::Error, fmt::{Display, Write as _}, io::{self, Write}};
+
+use crate::body::BodyTooLarge;
+use crate::header::HeaderMap;
+use crate::media_type;
+use crate::path::Path;
+use crate::request::HttpRequest;
+use crate::response::{ErrorResponse, HttpBody, Status};
+use crate::server::is_timeout;
+
+/// Deepest nesting of arrays and objects the parser accepts, so hostile input cannot overflow the stack
+const MAX_DEPTH: usize = 128;
+
+/// A JSON value.
+///
+/// Objects keep their members in the order they were inserted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum JsonValue {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Parses a complete JSON document
+    pub fn parse<S: AsRef<[u8]>>(input: S) -> Result<Self, JsonError> {
+        let mut parser = Parser { input: input.as_ref(), position: 0 };
+        parser.skip_whitespace();
+        let value = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        if parser.position < parser.input.len() {
+            return Err(parser.error("Unexpected data after the value"));
+        }
+        Ok(value)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The number, if it is a whole one that fits an `i64`
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Number(value) if value.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, JsonValue)]> {
+        match self {
+            JsonValue::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// The member `key` of an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.as_object()?
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the member `key` of an object, replacing the value it had.
+    ///
+    /// # Panics
+    ///
+    /// If the value is not an object.
+    pub fn insert<K: Into<String>, V: Into<JsonValue>>(&mut self, key: K, value: V) {
+        let JsonValue::Object(members) = self else {
+            panic!("Cannot insert a member into {:?}", self);
+        };
+        let key = key.into();
+        let value = value.into();
+        match members.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, member)) => *member = value,
+            None => members.push((key, value)),
+        }
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(value: bool) -> Self {
+        JsonValue::Bool(value)
+    }
+}
+
+macro_rules! from_numbers {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for JsonValue {
+                fn from(value: $ty) -> Self {
+                    JsonValue::Number(value as f64)
+                }
+            }
+        )*
+    };
+}
+
+from_numbers!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+impl From<String> for JsonValue {
+    fn from(value: String) -> Self {
+        JsonValue::String(value)
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> Self {
+        JsonValue::String(value.to_string())
+    }
+}
+
+impl <T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(JsonValue::Null)
+    }
+}
+
+impl <T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+    fn from(values: Vec<T>) -> Self {
+        JsonValue::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl <K: Into<String>, V: Into<JsonValue>> FromIterator<(K, V)> for JsonValue {
+    /// Builds an object out of its members
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        JsonValue::Object(dedup_members(iter.into_iter().map(|(key, value)| (key.into(), value.into()))))
+    }
+}
+
+/// Drops repeated members, keeping the position of the first and the value of the last.
+///
+/// Done in one pass at the end, as looking up every member while collecting
+/// them would make large objects quadratic.
+fn dedup_members<I: IntoIterator<Item = (String, JsonValue)>>(members: I) -> Vec<(String, JsonValue)> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut deduped: Vec<(String, JsonValue)> = Vec::new();
+    for (key, value) in members {
+        match positions.get(&key) {
+            Some(&position) => deduped[position].1 = value,
+            None => {
+                positions.insert(key.clone(), deduped.len());
+                deduped.push((key, value));
+            }
+        }
+    }
+    deduped
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in string.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{08}' => f.write_str("\\b")?,
+            '\u{0C}' => f.write_str("\\f")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Display for JsonValue {
+    /// Writes the value as compact JSON
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(value) => write!(f, "{}", value),
+            // JSON has no representation for infinities or NaN
+            JsonValue::Number(value) if !value.is_finite() => f.write_str("null"),
+            JsonValue::Number(value) => write!(f, "{}", value),
+            JsonValue::String(value) => write_string(f, value),
+            JsonValue::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            JsonValue::Object(members) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// Invalid JSON, with where in the input the problem was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonError {
+    pub message: String,
+    /// Byte offset into the input
+    pub offset: usize,
+    /// Line of the offset, starting at 1
+    pub line: usize,
+    /// Column of the offset in characters, starting at 1
+    pub column: usize,
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)
+    }
+}
+
+impl Error for JsonError {}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error<S: Into<String>>(&self, message: S) -> JsonError {
+        let before = &self.input[..self.position.min(self.input.len())];
+        let line_start = before.iter().rposition(|&b| b == b'\n').map(|i| i + 1).unwrap_or(0);
+        JsonError {
+            message: message.into(),
+            offset: self.position,
+            line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+            column: String::from_utf8_lossy(&before[line_start..]).chars().count() + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(b) if b == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(b) => Err(self.error(format!("Expected {:?}, found {:?}", expected as char, b as char))),
+            None => Err(self.error(format!("Expected {:?}, found the end of the input", expected as char))),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, JsonError> {
+        if !self.input[self.position..].starts_with(literal.as_bytes()) {
+            return Err(self.error("Invalid literal"));
+        }
+        self.position += literal.len();
+        Ok(value)
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        match self.peek() {
+            Some(b'n') => self.parse_literal("null", JsonValue::Null),
+            Some(b't') => self.parse_literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.parse_literal("false", JsonValue::Bool(false)),
+            Some(b'"') => self.parse_string().map(JsonValue::String),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(b'[' | b'{') if depth == MAX_DEPTH => Err(self.error(format!("Nested deeper than {} levels", MAX_DEPTH))),
+            Some(b'[') => self.parse_array(depth + 1),
+            Some(b'{') => self.parse_object(depth + 1),
+            Some(b) => Err(self.error(format!("Unexpected character {:?}", b as char))),
+            None => Err(self.error("Unexpected end of the input")),
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(values));
+        }
+
+        loop {
+            self.skip_whitespace();
+            values.push(self.parse_value(depth)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected a member name"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            let value = self.parse_value(depth)?;
+            members.push((key, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    // A repeated member keeps its last value
+                    return Ok(JsonValue::Object(dedup_members(members)));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_hex_escape(&mut self) -> Result<u32, JsonError> {
+        let digits = self.input.get(self.position..self.position + 4);
+        let code = digits
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("Expected four hex digits"))?;
+        self.position += 4;
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{08}',
+                        Some(b'f') => '\u{0C}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.position += 1;
+                            let escape_start = self.position;
+                            let mut code = self.parse_hex_escape()?;
+                            // Characters outside the BMP are escaped as a surrogate pair
+                            if (0xD800..0xDC00).contains(&code) && self.input[self.position..].starts_with(b"\\u") {
+                                self.position += 2;
+                                let low = self.parse_hex_escape()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    self.position = escape_start;
+                                    return Err(self.error("Invalid surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            let c = char::from_u32(code).ok_or_else(|| {
+                                self.position = escape_start;
+                                self.error("Unpaired surrogate")
+                            })?;
+                            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                            continue;
+                        }
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    };
+                    self.position += 1;
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(b) if b < 0x20 => return Err(self.error("Control character in string")),
+                Some(b) => {
+                    self.position += 1;
+                    bytes.push(b);
+                }
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in string"))
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            let from = parser.position;
+            while matches!(parser.peek(), Some(b'0'..=b'9')) {
+                parser.position += 1;
+            }
+            parser.position - from
+        };
+
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        match self.peek() {
+            // No leading zeros
+            Some(b'0') => self.position += 1,
+            Some(b'1'..=b'9') => {
+                digits(self);
+            }
+            _ => return Err(self.error("Expected a digit")),
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if digits(self) == 0 {
+                return Err(self.error("Expected a digit after the decimal point"));
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.position += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            if digits(self) == 0 {
+                return Err(self.error("Expected a digit in the exponent"));
+            }
+        }
+
+        // The grammar above only lets ASCII digits, signs, dots and exponents through
+        let number = std::str::from_utf8(&self.input[start..self.position]).unwrap_or_default();
+        number.parse().map(JsonValue::Number).map_err(|_| {
+            self.position = start;
+            self.error("Invalid number")
+        })
+    }
+}
+
+/// A response body holding a JSON document, sent with `Content-Type: application/json`
+#[derive(Debug, Clone)]
+pub struct Json {
+    body: String,
+}
+
+impl Json {
+    pub fn new<V: Into<JsonValue>>(value: V) -> Self {
+        Json { body: value.into().to_string() }
+    }
+
+    /// The serialized document
+    pub fn as_str(&self) -> &str {
+        &self.body
+    }
+}
+
+impl <V: Into<JsonValue>> From<V> for Json {
+    fn from(value: V) -> Self {
+        Json::new(value)
+    }
+}
+
+impl HttpBody for Json {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("Content-Type", "application/json");
+        headers.insert("Content-Length", self.body.len().to_string());
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self.body.as_bytes())
+    }
+}
+
+/// Failure to read a JSON request body
+#[derive(Debug)]
+pub enum JsonBodyError {
+    /// The request is not JSON, with the content type it declared
+    UnsupportedMediaType(Option<String>),
+    /// Reading the body failed
+    Io(io::Error),
+    /// The body is not valid JSON
+    Parse(JsonError),
+}
+
+impl ErrorResponse for JsonBodyError {
+    fn status(&self) -> Status {
+        match self {
+            JsonBodyError::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            JsonBodyError::Io(err) if BodyTooLarge::is(err) => Status::ContentTooLarge,
+            JsonBodyError::Io(err) if is_timeout(err) => Status::RequestTimeout,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+impl Display for JsonBodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonBodyError::UnsupportedMediaType(Some(content_type)) => write!(f, "Expected application/json, found {:?}", content_type),
+            JsonBodyError::UnsupportedMediaType(None) => write!(f, "Expected application/json, found no Content-Type"),
+            JsonBodyError::Io(err) => write!(f, "Failed to read the JSON body: {}", err),
+            JsonBodyError::Parse(err) => write!(f, "Invalid JSON: {}", err),
+        }
+    }
+}
+
+impl Error for JsonBodyError {}
+
+impl From<io::Error> for JsonBodyError {
+    fn from(err: io::Error) -> Self {
+        JsonBodyError::Io(err)
+    }
+}
+
+impl From<JsonError> for JsonBodyError {
+    fn from(err: JsonError) -> Self {
+        JsonBodyError::Parse(err)
+    }
+}
+
+/// Checks that `headers` declare a JSON body, as `application/json` or any `+json` type
+pub fn check_content_type(headers: &HeaderMap) -> Result<(), JsonBodyError> {
+    media_type::check_content_type(headers, |essence| essence == "application/json" || essence.ends_with("+json"))
+        .map(drop)
+        .map_err(JsonBodyError::UnsupportedMediaType)
+}
+
+/// Parses a JSON body sent with `headers`
+pub fn parse_json(headers: &HeaderMap, body: &[u8]) -> Result<JsonValue, JsonBodyError> {
+    check_content_type(headers)?;
+    Ok(JsonValue::parse(body)?)
+}
+
+impl <P: Path> HttpRequest<'_, P> {
+    /// Reads the rest of the body as JSON.
+    ///
+    /// The content type is checked before any of the body is read.
+    pub fn json(&mut self) -> Result<JsonValue, JsonBodyError> {
+        check_content_type(&self.headers)?;
+        let body = self.body.read_to_vec()?;
+        parse_json(&self.headers, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(JsonValue::parse(r#""\ud83d\ude00""#).unwrap(), JsonValue::from("\u{1F600}"));
+        assert_eq!(JsonValue::parse(r#""\u00e9""#).unwrap(), JsonValue::from("é"));
+        assert!(JsonValue::parse(r#""\ud83d""#).is_err());
+        assert!(JsonValue::parse(r#""\ude00""#).is_err());
+        assert!(JsonValue::parse(r#""\ud83dA""#).is_err());
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(JsonValue::parse("0").unwrap(), JsonValue::Number(0.0));
+        assert_eq!(JsonValue::parse("-0.5").unwrap(), JsonValue::Number(-0.5));
+        assert!(JsonValue::parse("01").is_err());
+        assert!(JsonValue::parse("-01").is_err());
+        assert!(JsonValue::parse("1.").is_err());
+        assert!(JsonValue::parse("1e").is_err());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(JsonValue::parse(nested(MAX_DEPTH)).is_ok());
+        let err = JsonValue::parse(nested(MAX_DEPTH + 1)).unwrap_err();
+        assert_eq!(err.offset, MAX_DEPTH);
+    }
+
+    #[test]
+    fn error_line_and_column() {
+        let err = JsonValue::parse("{\n  \"a\": 1,\n  \"é\": tru\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 8));
+        assert_eq!(err.offset, "{\n  \"a\": 1,\n  \"é\": ".len());
+
+        let err = JsonValue::parse("[1, 2] x").unwrap_err();
+        assert_eq!((err.line, err.column, err.offset), (1, 8, 7));
+    }
+
+    #[test]
+    fn round_trip_escaping() {
+        let text = "quote \" backslash \\ slash / newline \n tab \t bell \u{07} nul \u{0} emoji \u{1F600}";
+        let value = JsonValue::from(text);
+        let serialized = value.to_string();
+        assert!(!serialized.contains('\n'));
+        assert!(serialized.contains("\\u0007"));
+        assert_eq!(JsonValue::parse(&serialized).unwrap(), value);
+    }
+
+    #[test]
+    fn repeated_members() {
+        let value = JsonValue::parse(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+        assert_eq!(value.to_string(), r#"{"a":3,"b":2}"#);
+
+        let members: String = (0..50_000).map(|i| format!("\"k{}\":{},", i % 1000, i)).collect();
+        let value = JsonValue::parse(format!("{{{}\"end\":0}}", members)).unwrap();
+        assert_eq!(value.as_object().unwrap().len(), 1001);
+        assert_eq!(value.get("k999").and_then(JsonValue::as_i64), Some(49_999));
+    }
+}
@@ -1,62 +1,305 @@
 pub mod path;
+pub mod body;
+pub mod header;
+pub mod media_type;
+pub mod cookie;
+pub mod date;
+mod chunked;
 pub mod request;
 pub mod response;
+pub mod status;
 pub mod query;
+pub mod from_query;
+pub mod form;
+pub mod multipart;
+pub mod json;
+pub mod file;
+pub mod mime;
+pub mod resolve;
+pub mod autoindex;
+pub mod static_files;
 pub mod router;
+pub mod file_based_routing;
+pub mod config;
+pub mod pool;
+pub mod server;
 
 use crate::router::RequestHandler;
 
 use std::{
-    net::{TcpListener, ToSocketAddrs, TcpStream}, 
-    io::{prelude::*, BufReader}, collections::HashMap};
+    net::{ToSocketAddrs, TcpStream},
+    io::{self, prelude::*, BufReader},
+    panic::{self, AssertUnwindSafe},
+    time::Instant};
 
+use config::ServerConfig;
+use server::{is_timeout, Connection, Server, TimedReader};
+
+use body::{BodyReader, RequestBody};
+use header::HeaderMap;
 use path::Path;
-use query::QueryPath;
-use request::HttpRequest;
-use response::{HttpResponse, HttpBody};
+use request::{HttpRequest, Method, ParseError};
+use response::{HttpBody, HttpResponse, HttpVersion, Status};
+
+fn read_line<R: BufRead>(reader: &mut R, limit: usize, too_long: fn() -> ParseError) -> Result<Option<Vec<u8>>, ParseError> {
+    let mut line = Vec::new();
+    // The limit leaves out the CRLF ending the line
+    let budget = limit as u64 + 2;
+    let read = reader.take(budget).read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    // Using up the budget before the LF means the line goes on past the limit
+    if read as u64 == budget && line.last() != Some(&b'\n') {
+        return Err(too_long());
+    }
+
+    while line.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+        line.pop();
+    }
+    if line.len() > limit {
+        return Err(too_long());
+    }
+    Ok(Some(line))
+}
 
-fn handle_request(request: HttpRequest<QueryPath<String>>) -> HttpResponse<Box<dyn HttpBody>> {
-    //let mut router = Router::new("./server");
+/// The request line and the raw header lines of a request
+type RequestHead = (String, Vec<Vec<u8>>);
 
-    /*
-    let default_name = "(use name query parameter)".to_string();
-    let name = request.path.query.get(&"name".to_string()).unwrap_or(&default_name);
+fn read_request_head<R: BufRead>(reader: &mut R, config: &ServerConfig) -> Result<Option<RequestHead>, ParseError> {
+    // Skip the empty lines some clients send between requests
+    let status_line = loop {
+        match read_line(reader, config.max_request_line_length, || ParseError::RequestLineTooLong)? {
+            Some(line) if line.is_empty() => continue,
+            Some(line) => break String::from_utf8(line).map_err(|_| ParseError::InvalidEncoding)?,
+            None => return Ok(None),
+        }
+    };
 
-    let body = match request.path.path.as_str() {
-        "/" => format!("Hello, {}", &name),
-        _ => "Good morning".to_string(),
-    }.to_string();
+    let mut header_lines = Vec::new();
+    let mut header_size = 0;
+    loop {
+        let remaining = config.max_header_size.saturating_sub(header_size);
+        let line = match read_line(reader, remaining, || ParseError::HeadersTooLarge)? {
+            Some(line) if line.is_empty() => break,
+            Some(line) => line,
+            None => return Ok(None),
+        };
 
-    HttpResponse::new(response::HttpVersion::Http1_1, Status::Ok, HashMap::new(), body)
-    */
+        if header_lines.len() == config.max_header_count {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        header_size += line.len();
+        header_lines.push(line);
+    }
 
-    // router.handle_request(request)
-    HttpResponse::new(response::HttpVersion::Http1_1, response::Status::Ok, HashMap::new(), Box::new("asd".to_string()))
+    Ok(Some((status_line, header_lines)))
 }
 
-fn handle_stream<P: Path, B: HttpBody>(mut stream: TcpStream, request_handler: RequestHandler<P, B>) {
-    let reader = BufReader::new(&mut stream);
-    let mut lines = reader
-        .lines()
-        .map(|result| result.unwrap())
-        .take_while(|line| !line.is_empty());
+fn error_response(status: Status, message: String) -> HttpResponse<String> {
+    let headers = HeaderMap::from([("Content-Type", "text/plain; charset=utf-8")]);
+    HttpResponse::new(HttpVersion::Http1_1, status, headers, message)
+}
 
-    let status_line = lines.next().unwrap();
-    let header_lines: Vec<_> = lines.collect();
-    let request = HttpRequest::parse_request(status_line, header_lines);
-    println!("{:#?}", &request);
+/// Answers a request the server could not hand to the request handler, then gives up on the connection.
+///
+/// `http_version` is the version of the request, or HTTP/1.1 when it could not be read.
+fn write_error(stream: &mut TcpStream, status: Status, message: String, http_version: HttpVersion) {
+    let response = error_response(status, message);
+    // The connection is closed right after, there is nothing left to do if this fails
+    send_response(stream, response, http_version, false, false);
+}
 
-    let mut response = request_handler(request);
+/// Answers an `OPTIONS` request with the methods allowed at its path
+fn options_response(methods: Vec<Method>) -> HttpResponse<String> {
+    let headers = HeaderMap::from([("Allow", request::allow_header(&methods))]);
+    HttpResponse::new(HttpVersion::Http1_1, Status::NoContent, headers, String::new())
+}
 
-    response.write(&mut stream);
+fn closes_connection<B: HttpBody>(response: &HttpResponse<B>) -> bool {
+    response
+        .header("Connection")
+        .is_some_and(|connection| connection.as_bytes().eq_ignore_ascii_case(b"close"))
 }
 
-pub fn run_server<A: ToSocketAddrs>(addr: A) -> ! {
-    let listener = TcpListener::bind(addr).unwrap();
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
-        handle_stream(stream, handle_request);
+/// Writes a response in a version the client understands, returning whether the connection stays open after it
+fn send_response<B: HttpBody>(stream: &mut TcpStream, mut response: HttpResponse<B>, http_version: HttpVersion, keep_alive: bool, head: bool) -> bool {
+    if response.http_version() > http_version {
+        response.set_http_version(http_version);
+    }
+
+    let keep_alive = keep_alive && !closes_connection(&response);
+    response.set_header("Connection", if keep_alive { "keep-alive" } else { "close" });
+
+    let written = match head {
+        true => response.write_head(stream).and_then(|_| stream.flush()),
+        false => response.send(stream),
+    };
+    // A connection that failed mid-response is unusable, and writing may have given up on
+    // keeping the connection when the body has no length
+    written.is_ok() && keep_alive && !closes_connection(&response)
+}
+
+fn handle_stream<P: Path, B: HttpBody, H: RequestHandler<P, B>>(mut stream: TcpStream, request_handler: &H, config: &ServerConfig, connection: &Connection) {
+    let mut reader = match stream.try_clone() {
+        Ok(stream) => BodyReader::new(BufReader::new(TimedReader::new(stream))),
+        Err(_) => return,
+    };
+    if stream.set_write_timeout(Some(config.write_timeout)).is_err() {
+        return;
+    }
+
+    for served in 1..=config.max_requests_per_connection {
+        if !connection.idle() {
+            return;
+        }
+
+        // Wait for the first byte of the next request
+        reader.inner_mut().get_mut().set_deadline(Instant::now() + config.keep_alive_timeout);
+        match reader.inner_mut().fill_buf() {
+            Ok(buffer) if !buffer.is_empty() => (),
+            // The client closed the connection or went idle for too long
+            _ => return,
+        }
+        connection.busy();
+
+        reader.inner_mut().get_mut().set_deadline(Instant::now() + config.header_read_timeout);
+        let (status_line, header_lines) = match read_request_head(reader.inner_mut(), config) {
+            Ok(Some(head)) => head,
+            Ok(None) => return,
+            Err(ParseError::Io(err)) if is_timeout(&err) => {
+                return write_error(&mut stream, Status::RequestTimeout, "Timed out reading the request".to_string(), HttpVersion::Http1_1);
+            }
+            Err(ParseError::Io(_)) => return,
+            Err(err) => return write_error(&mut stream, err.status(), err.to_string(), HttpVersion::Http1_1),
+        };
+
+        if reader.inner_mut().get_mut().set_timeout(config.body_read_timeout).is_err() {
+            return;
+        }
+
+        let mut request = match HttpRequest::parse_request(status_line, header_lines) {
+            Ok(request) => request,
+            Err(err) => return write_error(&mut stream, err.status(), err.to_string(), HttpVersion::Http1_1),
+        };
+
+        let http_version = request.http_version;
+        let keep_alive = request.keep_alive() && served < config.max_requests_per_connection;
+
+        let head = request.method == Method::Head;
+        if head {
+            // HEAD runs whatever serves GET, the body is left out when writing the response
+            request.method = Method::Get;
+        }
+        let allowed_methods = match request.method {
+            Method::Options => request_handler.allowed_methods(&request),
+            _ => None,
+        };
+
+        let max_body_size = request_handler.max_body_size(&request).unwrap_or(config.max_body_size);
+
+        // Transfer-Encoding wins over Content-Length, but an intermediary trusting Content-Length would
+        // see the request end elsewhere, so the connection is not reused after it (RFC 9112 section 6.1)
+        let conflicting_length = request.transfer_encoding().is_some() && request.headers.contains("Content-Length");
+
+        if let Some(transfer_encoding) = request.transfer_encoding() {
+            if http_version == HttpVersion::Http1_0 {
+                // HTTP/1.0 has no transfer codings, the framing of such a request cannot be trusted
+                let message = "Transfer-Encoding is not allowed in HTTP/1.0 requests".to_string();
+                return write_error(&mut stream, Status::BadRequest, message, http_version);
+            }
+            if !transfer_encoding.trim().eq_ignore_ascii_case("chunked") {
+                let message = format!("Unsupported Transfer-Encoding {:?}", transfer_encoding);
+                return write_error(&mut stream, Status::NotImplemented, message, http_version);
+            }
+
+            reader.start_chunked(max_body_size);
+            request.body = RequestBody::new(&mut reader, None);
+        } else {
+            let content_length = match request.content_length() {
+                Ok(length) => length.unwrap_or(0),
+                Err(err) => return write_error(&mut stream, err.status(), err.to_string(), http_version),
+            };
+            if content_length > max_body_size {
+                let err = ParseError::BodyTooLarge(max_body_size);
+                return write_error(&mut stream, err.status(), err.to_string(), http_version);
+            }
+            reader.start(content_length);
+            request.body = RequestBody::new(&mut reader, Some(content_length));
+        }
+
+        let keep_alive = keep_alive && !conflicting_length && !connection.is_shutting_down();
+        let keep_alive = match allowed_methods {
+            Some(methods) => send_response(&mut stream, options_response(methods), http_version, keep_alive, false),
+            None => match panic::catch_unwind(AssertUnwindSafe(|| request_handler.handle(request))) {
+                Ok(response) => send_response(&mut stream, response, http_version, keep_alive, head),
+                // The handler may have stopped halfway through the body, so the connection is not reused
+                Err(_) => {
+                    let response = error_response(Status::InternalServerError, "Internal server error".to_string());
+                    send_response(&mut stream, response, http_version, false, head)
+                }
+            },
+        };
+
+        if !keep_alive || reader.discard().is_err() {
+            return;
+        }
     }
-    panic!("Listener closed");
 }
 
+pub fn run_server<A, P, B, H>(addr: A, request_handler: H) -> io::Result<()>
+where
+    A: ToSocketAddrs,
+    P: Path + 'static,
+    B: HttpBody + 'static,
+    H: RequestHandler<P, B> + Send + Sync + 'static,
+{
+    run_server_with_config(addr, request_handler, ServerConfig::default())
+}
+
+pub fn run_server_with_config<A, P, B, H>(addr: A, request_handler: H, config: ServerConfig) -> io::Result<()>
+where
+    A: ToSocketAddrs,
+    P: Path + 'static,
+    B: HttpBody + 'static,
+    H: RequestHandler<P, B> + Send + Sync + 'static,
+{
+    Server::bind(addr, request_handler, config)?.wait();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_head(input: &str, config: &ServerConfig) -> Result<Option<RequestHead>, ParseError> {
+        read_request_head(&mut input.as_bytes(), config)
+    }
+
+    #[test]
+    fn request_line_limit_leaves_out_crlf() {
+        let config = ServerConfig { max_request_line_length: 20, ..ServerConfig::default() };
+        let line = format!("GET /{} HTTP/1.1", "a".repeat(6));
+        assert_eq!(line.len(), 20);
+
+        let (status_line, _) = read_head(&format!("{}\r\n\r\n", line), &config).unwrap().unwrap();
+        assert_eq!(status_line, line);
+        assert!(read_head(&format!("{}\n\n", line), &config).unwrap().is_some());
+
+        let longer = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(7));
+        assert!(matches!(read_head(&longer, &config), Err(ParseError::RequestLineTooLong)));
+        let longer = format!("GET /{} HTTP/1.1\r\r\n\r\n", "a".repeat(6));
+        assert!(matches!(read_head(&longer, &config), Err(ParseError::RequestLineTooLong)));
+    }
+
+    #[test]
+    fn header_size_limit_leaves_out_crlf() {
+        let config = ServerConfig { max_header_size: 20, ..ServerConfig::default() };
+
+        let (_, header_lines) = read_head("GET / HTTP/1.1\r\nA: 123456\r\nB: 12345678\r\n\r\n", &config).unwrap().unwrap();
+        assert_eq!(header_lines, [b"A: 123456".to_vec(), b"B: 12345678".to_vec()]);
+
+        let longer = "GET / HTTP/1.1\r\nA: 123456\r\nB: 123456789\r\n\r\n";
+        assert!(matches!(read_head(longer, &config), Err(ParseError::HeadersTooLarge)));
+    }
+}
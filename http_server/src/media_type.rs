@@ -0,0 +1,124 @@
+use crate::header::HeaderMap;
+
+/// Splits a `Content-Type` or `Content-Disposition` field into its value and parameters.
+///
+/// Quoted parameter values are unquoted, and may hold `;` and escaped quotes.
+pub(crate) fn split_parameters(field: &str) -> (&str, Vec<(&str, String)>) {
+    let (value, mut rest) = field.split_once(';').unwrap_or((field, ""));
+    let mut parameters = Vec::new();
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            return (value.trim(), parameters);
+        }
+
+        let name_end = rest.find(['=', ';']).unwrap_or(rest.len());
+        let name = rest[..name_end].trim();
+        // A parameter without a value is skipped
+        let Some(after_name) = rest[name_end..].strip_prefix('=') else {
+            rest = &rest[name_end..];
+            continue;
+        };
+        let after_name = after_name.trim_start();
+
+        match after_name.strip_prefix('"') {
+            Some(quoted) => {
+                let (unquoted, end) = unquote(quoted);
+                parameters.push((name, unquoted));
+                // Anything between the closing quote and the next parameter is ignored
+                let after_quote = &quoted[end..];
+                rest = after_quote.find(';').map_or("", |i| &after_quote[i..]);
+            }
+            None => {
+                let end = after_name.find(';').unwrap_or(after_name.len());
+                parameters.push((name, after_name[..end].trim().to_string()));
+                rest = &after_name[end..];
+            }
+        }
+    }
+}
+
+/// Reads a quoted string up to its closing quote, resolving its escapes.
+///
+/// `quoted` starts right after the opening quote. Returns the unquoted value
+/// and where the rest of the input starts, past the closing quote.
+fn unquote(quoted: &str) -> (String, usize) {
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next().map(|(_, c)| c)),
+            '"' => return (unquoted, i + 1),
+            c => unquoted.push(c),
+        }
+    }
+    // An unterminated string runs to the end of the field
+    (unquoted, quoted.len())
+}
+
+/// A `Content-Type`, split into its media type and parameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    /// The type and subtype, lowercased, like `application/json`
+    pub essence: String,
+    pub parameters: Vec<(String, String)>,
+}
+
+impl MediaType {
+    pub fn parse(field: &str) -> Self {
+        let (essence, parameters) = split_parameters(field);
+        MediaType {
+            essence: essence.to_ascii_lowercase(),
+            parameters: parameters.into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+        }
+    }
+
+    /// The value of the parameter `name`, which is matched case-insensitively
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Checks the `Content-Type` of `headers` with `accepts`, which is given its lowercased media type.
+///
+/// Fails with the field as it was sent, or `None` if there is none, for the error to quote.
+pub fn check_content_type<F: Fn(&str) -> bool>(headers: &HeaderMap, accepts: F) -> Result<MediaType, Option<String>> {
+    let Some(content_type) = headers.get("Content-Type").map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned()) else {
+        return Err(None);
+    };
+
+    let media_type = MediaType::parse(&content_type);
+    match accepts(&media_type.essence) {
+        true => Ok(media_type),
+        false => Err(Some(content_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_parameters() {
+        let media_type = MediaType::parse("Multipart/Form-Data; charset=utf-8 ; Boundary=\"x;y \\\"z\\\"\"; flag; q=1");
+        assert_eq!(media_type.essence, "multipart/form-data");
+        assert_eq!(media_type.parameter("charset"), Some("utf-8"));
+        assert_eq!(media_type.parameter("boundary"), Some("x;y \"z\""));
+        assert_eq!(media_type.parameter("flag"), None);
+        assert_eq!(media_type.parameter("q"), Some("1"));
+    }
+
+    #[test]
+    fn checks_content_type() {
+        let headers = HeaderMap::from([("Content-Type", "Application/JSON; charset=utf-8")]);
+        assert!(check_content_type(&headers, |essence| essence == "application/json").is_ok());
+        assert_eq!(
+            check_content_type(&headers, |essence| essence == "text/plain"),
+            Err(Some("Application/JSON; charset=utf-8".to_string())),
+        );
+        assert_eq!(check_content_type(&HeaderMap::new(), |_| true), Err(None));
+    }
+}
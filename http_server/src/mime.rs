@@ -0,0 +1,122 @@
+use std::{collections::HashMap, path::Path};
+
+/// Media types of common file extensions
+const BUILT_IN: &[(&str, &str)] = &[
+    // Text
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("xml", "application/xml"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+
+    // Images
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+
+    // Fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+
+    // Audio and video
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+
+    // Documents and archives
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+];
+
+const DEFAULT_TYPE: &str = "application/octet-stream";
+
+/// Whether a media type is text, which makes its charset worth declaring
+fn is_text(media_type: &str) -> bool {
+    let media_type = media_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    media_type.starts_with("text/")
+        || media_type.ends_with("+json")
+        || media_type.ends_with("+xml")
+        || matches!(media_type.as_str(), "application/json" | "application/xml" | "application/javascript")
+}
+
+/// Maps file extensions to the `Content-Type` files are served with.
+///
+/// Starts out with a table of common types, which can be overridden or
+/// extended. Text types are given `charset=utf-8` unless they name a charset
+/// of their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeTypes {
+    types: HashMap<String, String>,
+    default: String,
+}
+
+impl MimeTypes {
+    pub fn new() -> Self {
+        MimeTypes {
+            types: BUILT_IN
+                .iter()
+                .map(|(extension, media_type)| (extension.to_string(), media_type.to_string()))
+                .collect(),
+            default: DEFAULT_TYPE.to_string(),
+        }
+    }
+
+    /// Serves files ending in `.extension` as `media_type`, replacing any type it had
+    pub fn insert<E: AsRef<str>, M: Into<String>>(&mut self, extension: E, media_type: M) {
+        let extension = extension.as_ref().trim_start_matches('.').to_ascii_lowercase();
+        self.types.insert(extension, media_type.into());
+    }
+
+    pub fn with<E: AsRef<str>, M: Into<String>>(mut self, extension: E, media_type: M) -> Self {
+        self.insert(extension, media_type);
+        self
+    }
+
+    /// The type of files whose extension is unknown, `application/octet-stream` by default
+    pub fn with_default<M: Into<String>>(mut self, media_type: M) -> Self {
+        self.default = media_type.into();
+        self
+    }
+
+    /// The `Content-Type` to serve the file at `path` with
+    pub fn content_type<P: AsRef<Path>>(&self, path: P) -> String {
+        let media_type = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.types.get(&extension.to_ascii_lowercase()))
+            .unwrap_or(&self.default);
+
+        if is_text(media_type) && !media_type.to_ascii_lowercase().contains("charset=") {
+            format!("{}; charset=utf-8", media_type)
+        } else {
+            media_type.clone()
+        }
+    }
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        MimeTypes::new()
+    }
+}
@@ -0,0 +1,569 @@
+use std::{
+    env,
+    error::Error,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path as FsPath, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::body::{BodyTooLarge, RequestBody};
+use crate::header::HeaderMap;
+use crate::media_type::{check_content_type, split_parameters};
+use crate::path::Path;
+use crate::request::HttpRequest;
+use crate::response::{ErrorResponse, Status};
+use crate::server::is_timeout;
+
+const MULTIPART_CONTENT_TYPE: &str = "multipart/form-data";
+
+#[derive(Debug)]
+pub enum MultipartError {
+    /// The request is not `multipart/form-data`, with the content type it declared
+    UnsupportedMediaType(Option<String>),
+    /// The content type has no usable `boundary` parameter
+    InvalidBoundary,
+    /// The body does not follow the multipart format
+    Malformed(String),
+    /// The headers of a part are larger than the limit
+    HeadersTooLarge(usize),
+    /// A part is larger than the limit
+    PartTooLarge(u64),
+    /// The whole body is larger than the limit
+    TooLarge(u64),
+    /// The body has more parts than the limit
+    TooManyParts(usize),
+    /// Reading the body or writing a part failed
+    Io(io::Error),
+}
+
+impl ErrorResponse for MultipartError {
+    fn status(&self) -> Status {
+        match self {
+            MultipartError::UnsupportedMediaType(_) => Status::UnsupportedMediaType,
+            MultipartError::HeadersTooLarge(_)
+            | MultipartError::PartTooLarge(_)
+            | MultipartError::TooLarge(_)
+            | MultipartError::TooManyParts(_) => Status::ContentTooLarge,
+            MultipartError::Io(err) if BodyTooLarge::is(err) => Status::ContentTooLarge,
+            MultipartError::Io(err) if is_timeout(err) => Status::RequestTimeout,
+            MultipartError::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => Status::BadRequest,
+            // Anything else went wrong on our side, like a full disk
+            MultipartError::Io(_) => Status::InternalServerError,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultipartError::UnsupportedMediaType(Some(content_type)) => write!(f, "Expected {}, found {:?}", MULTIPART_CONTENT_TYPE, content_type),
+            MultipartError::UnsupportedMediaType(None) => write!(f, "Expected {}, found no Content-Type", MULTIPART_CONTENT_TYPE),
+            MultipartError::InvalidBoundary => write!(f, "Missing or invalid multipart boundary"),
+            MultipartError::Malformed(reason) => write!(f, "Malformed multipart body: {}", reason),
+            MultipartError::HeadersTooLarge(limit) => write!(f, "The headers of a part are larger than {} bytes", limit),
+            MultipartError::PartTooLarge(limit) => write!(f, "A part is larger than {} bytes", limit),
+            MultipartError::TooLarge(limit) => write!(f, "The multipart body is larger than {} bytes", limit),
+            MultipartError::TooManyParts(limit) => write!(f, "The multipart body has more than {} parts", limit),
+            MultipartError::Io(err) => write!(f, "Failed to read the multipart body: {}", err),
+        }
+    }
+}
+
+impl Error for MultipartError {}
+
+impl From<io::Error> for MultipartError {
+    /// Unwraps the limit errors raised while reading a part through `Read`
+    fn from(err: io::Error) -> Self {
+        if !err.get_ref().is_some_and(|inner| inner.is::<MultipartError>()) {
+            return MultipartError::Io(err);
+        }
+        match err.into_inner().map(|inner| inner.downcast::<MultipartError>()) {
+            Some(Ok(inner)) => *inner,
+            Some(Err(inner)) => MultipartError::Io(io::Error::other(inner)),
+            None => unreachable!("checked for an inner error above"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipartLimits {
+    /// Largest size of the body of a single part (10 MiB)
+    pub max_part_size: u64,
+    /// Largest size of the whole multipart body, delimiters and headers included (50 MiB)
+    pub max_total_size: u64,
+    /// Largest size of the headers of a single part (8 KiB)
+    pub max_header_size: usize,
+    /// Most parts a body can have (100)
+    pub max_parts: usize,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        MultipartLimits {
+            max_part_size: 10 * 1024 * 1024,
+            max_total_size: 50 * 1024 * 1024,
+            max_header_size: 8 * 1024,
+            max_parts: 100,
+        }
+    }
+}
+
+/// The boundary of a `multipart/form-data` body sent with `headers`
+pub fn boundary(headers: &HeaderMap) -> Result<String, MultipartError> {
+    let media_type = check_content_type(headers, |essence| essence == MULTIPART_CONTENT_TYPE)
+        .map_err(MultipartError::UnsupportedMediaType)?;
+
+    match media_type.parameter("boundary") {
+        Some(boundary) if (1..=70).contains(&boundary.len()) => Ok(boundary.to_string()),
+        _ => Err(MultipartError::InvalidBoundary),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the first delimiter
+    Preamble,
+    /// Within the body of a part
+    Body,
+    /// Right after a delimiter, before the headers of the next part or the end marker
+    Delimiter,
+    Done,
+}
+
+/// A `multipart/form-data` body, read one part at a time as it arrives.
+///
+/// ```no_run
+/// # fn example(body: &[u8], boundary: &str) -> Result<(), http_server::multipart::MultipartError> {
+/// let mut multipart = http_server::multipart::Multipart::new(body, boundary);
+/// while let Some(mut part) = multipart.next_part()? {
+///     if part.filename().is_some() {
+///         let file = part.save_temp()?;
+///         println!("{:?} saved to {:?}", part.name(), file.path());
+///     } else {
+///         let text = part.text()?;
+///         println!("{:?} = {:?}", part.name(), text);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Multipart<R: Read> {
+    reader: R,
+    /// Bytes read from `reader` but not consumed yet
+    buffer: Vec<u8>,
+    /// `CRLF--boundary`, which ends every part
+    delimiter: Vec<u8>,
+    state: State,
+    limits: MultipartLimits,
+    total_read: u64,
+    parts: usize,
+}
+
+impl <R: Read> Multipart<R> {
+    pub fn new<S: AsRef<str>>(reader: R, boundary: S) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_ref().as_bytes());
+
+        Multipart {
+            reader,
+            // The first delimiter may start the body without a line break before it
+            buffer: b"\r\n".to_vec(),
+            delimiter,
+            state: State::Preamble,
+            limits: MultipartLimits::default(),
+            total_read: 0,
+            parts: 0,
+        }
+    }
+
+    /// Reads the body sent with `headers`, after checking it is `multipart/form-data`
+    pub fn from_headers(headers: &HeaderMap, reader: R) -> Result<Self, MultipartError> {
+        Ok(Multipart::new(reader, boundary(headers)?))
+    }
+
+    pub fn with_limits(mut self, limits: MultipartLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Reads more of the body into the buffer, returning false at the end of the body
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; 8 * 1024];
+        let read = self.reader.read(&mut chunk)?;
+        self.total_read += read as u64;
+        if self.total_read > self.limits.max_total_size {
+            return Err(io::Error::other(MultipartError::TooLarge(self.limits.max_total_size)));
+        }
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(read > 0)
+    }
+
+    fn unexpected_end() -> io::Error {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "The multipart body ended before its closing delimiter")
+    }
+
+    /// Reads part data up to the next delimiter, returning 0 once it is reached
+    fn read_data(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let available = match find(&self.buffer, &self.delimiter) {
+                Some(0) => {
+                    self.buffer.drain(..self.delimiter.len());
+                    self.state = State::Delimiter;
+                    return Ok(0);
+                }
+                Some(index) => index,
+                // The end of the buffer could be the start of a delimiter
+                None => self.buffer.len().saturating_sub(self.delimiter.len() - 1),
+            };
+
+            if available > 0 {
+                let read = available.min(buf.len());
+                buf[..read].copy_from_slice(&self.buffer[..read]);
+                self.buffer.drain(..read);
+                return Ok(read);
+            }
+
+            if !self.fill()? {
+                return Err(Multipart::<R>::unexpected_end());
+            }
+        }
+    }
+
+    /// Reads a line, without its line break
+    fn read_line(&mut self, limit: usize) -> Result<Vec<u8>, MultipartError> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+                if end > limit {
+                    return Err(MultipartError::HeadersTooLarge(self.limits.max_header_size));
+                }
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(line);
+            }
+
+            if self.buffer.len() > limit {
+                return Err(MultipartError::HeadersTooLarge(self.limits.max_header_size));
+            }
+            if !self.fill()? {
+                return Err(Multipart::<R>::unexpected_end().into());
+            }
+        }
+    }
+
+    fn read_headers(&mut self) -> Result<HeaderMap, MultipartError> {
+        let mut headers = HeaderMap::new();
+        let mut size = 0;
+        loop {
+            let line = self.read_line(self.limits.max_header_size - size)?;
+            if line.is_empty() {
+                return Ok(headers);
+            }
+            size += line.len() + 2;
+            if size > self.limits.max_header_size {
+                return Err(MultipartError::HeadersTooLarge(self.limits.max_header_size));
+            }
+
+            let colon = line.iter().position(|&b| b == b':');
+            let name = colon.and_then(|colon| std::str::from_utf8(&line[..colon]).ok());
+            match (colon, name) {
+                (Some(colon), Some(name)) if !name.trim().is_empty() => {
+                    headers.append(name.trim(), line[colon + 1..].trim_ascii());
+                }
+                _ => {
+                    let line = String::from_utf8_lossy(&line).into_owned();
+                    return Err(MultipartError::Malformed(format!("Invalid part header {:?}", line)));
+                }
+            }
+        }
+    }
+
+    /// Moves on to the next part, skipping whatever is left of the current one.
+    ///
+    /// Returns `None` after the last part.
+    pub fn next_part(&mut self) -> Result<Option<Part<'_, R>>, MultipartError> {
+        loop {
+            match self.state {
+                State::Preamble | State::Body => {
+                    let mut scratch = [0; 8 * 1024];
+                    while self.read_data(&mut scratch)? > 0 {}
+                }
+                State::Delimiter => {
+                    while self.buffer.len() < 2 {
+                        if !self.fill()? {
+                            return Err(Multipart::<R>::unexpected_end().into());
+                        }
+                    }
+                    if self.buffer.starts_with(b"--") {
+                        // Whatever follows the closing delimiter is ignored
+                        self.state = State::Done;
+                        return Ok(None);
+                    }
+
+                    let padding = self.read_line(self.limits.max_header_size)?;
+                    if !padding.iter().all(|&b| b == b' ' || b == b'\t') {
+                        return Err(MultipartError::Malformed("Unexpected data after a delimiter".to_string()));
+                    }
+
+                    self.parts += 1;
+                    if self.parts > self.limits.max_parts {
+                        return Err(MultipartError::TooManyParts(self.limits.max_parts));
+                    }
+
+                    let headers = self.read_headers()?;
+                    self.state = State::Body;
+                    return Ok(Some(Part::new(self, headers)));
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+/// A part of a multipart body, whose content is read through `Read`
+pub struct Part<'a, R: Read> {
+    multipart: &'a mut Multipart<R>,
+    headers: HeaderMap,
+    name: Option<String>,
+    filename: Option<String>,
+    read: u64,
+}
+
+impl <'a, R: Read> Part<'a, R> {
+    fn new(multipart: &'a mut Multipart<R>, headers: HeaderMap) -> Self {
+        let disposition = headers.get("Content-Disposition").map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+        let mut name = None;
+        let mut filename = None;
+        if let Some(disposition) = &disposition {
+            let (_, parameters) = split_parameters(disposition);
+            for (key, value) in parameters {
+                if key.eq_ignore_ascii_case("name") {
+                    name = Some(value);
+                } else if key.eq_ignore_ascii_case("filename") {
+                    filename = Some(value);
+                }
+            }
+        }
+
+        Part { multipart, headers, name, filename, read: 0 }
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The name of the form field, from `Content-Disposition`
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The name of the uploaded file, as the client gave it.
+    ///
+    /// This comes straight from the client and must not be used as a path as is.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get_str("Content-Type")
+    }
+
+    /// Reads the rest of the part into memory
+    pub fn read_to_vec(&mut self) -> Result<Vec<u8>, MultipartError> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads the rest of the part as UTF-8 text
+    pub fn text(&mut self) -> Result<String, MultipartError> {
+        let name = self.name.clone().unwrap_or_default();
+        String::from_utf8(self.read_to_vec()?).map_err(|_| MultipartError::Malformed(format!("Part {:?} is not valid UTF-8", name)))
+    }
+
+    /// Streams the rest of the part into `writer`, returning how many bytes were written
+    pub fn copy_to<W: Write + ?Sized>(&mut self, writer: &mut W) -> Result<u64, MultipartError> {
+        Ok(io::copy(self, writer)?)
+    }
+
+    /// Streams the rest of the part into a new file in the system's temporary directory
+    pub fn save_temp(&mut self) -> Result<TempFile, MultipartError> {
+        self.save_temp_in(env::temp_dir())
+    }
+
+    /// Streams the rest of the part into a new file in `dir`.
+    ///
+    /// The file is deleted when the returned `TempFile` is dropped, unless it is persisted.
+    pub fn save_temp_in<D: AsRef<FsPath>>(&mut self, dir: D) -> Result<TempFile, MultipartError> {
+        let (mut temp_file, mut file) = TempFile::create(dir.as_ref())?;
+        temp_file.size = self.copy_to(&mut file)?;
+        file.sync_all()?;
+        Ok(temp_file)
+    }
+}
+
+impl <R: Read> Read for Part<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.multipart.state != State::Body {
+            return Ok(0);
+        }
+
+        let read = self.multipart.read_data(buf)?;
+        self.read += read as u64;
+        if self.read > self.multipart.limits.max_part_size {
+            return Err(io::Error::other(MultipartError::PartTooLarge(self.multipart.limits.max_part_size)));
+        }
+        Ok(read)
+    }
+}
+
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// An uploaded file saved to disk, deleted when dropped unless persisted
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    size: u64,
+    persisted: bool,
+}
+
+impl TempFile {
+    fn create(dir: &FsPath) -> io::Result<(TempFile, File)> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or(0);
+        loop {
+            let count = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!("upload-{}-{}-{}", process::id(), nanos, count));
+            // Never reuse a file that is already there, someone else may be holding it
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((TempFile { path, size: 0, persisted: false }, file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &FsPath {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Moves the file to `path` so it outlives the `TempFile`
+    pub fn persist<P: AsRef<FsPath>>(mut self, path: P) -> io::Result<()> {
+        fs::rename(&self.path, path)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl <'a, P: Path> HttpRequest<'a, P> {
+    /// Reads the body as `multipart/form-data`, after checking the content type
+    pub fn multipart(&mut self) -> Result<Multipart<&mut RequestBody<'a>>, MultipartError> {
+        Multipart::from_headers(&self.headers, &mut self.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "XyZzy";
+
+    fn multipart(body: &str) -> Multipart<&[u8]> {
+        Multipart::new(body.as_bytes(), BOUNDARY)
+    }
+
+    /// The name, filename and text of a part
+    type PartFields = (Option<String>, Option<String>, String);
+
+    fn read_all(mut multipart: Multipart<&[u8]>) -> Result<Vec<PartFields>, MultipartError> {
+        let mut parts = Vec::new();
+        while let Some(mut part) = multipart.next_part()? {
+            let name = part.name().map(str::to_string);
+            let filename = part.filename().map(str::to_string);
+            parts.push((name, filename, part.text()?));
+        }
+        Ok(parts)
+    }
+
+    #[test]
+    fn skips_preamble_and_epilogue() {
+        let body = "This is the preamble.\r\n--XyZzy\r\n\
+            Content-Disposition: form-data; name=\"a\"\r\n\r\n\
+            first\r\n--XyZzy\r\n\
+            Content-Disposition: form-data; name=\"b\"\r\n\r\n\
+            second\r\nline\r\n--XyZzy--\r\nThis is the epilogue.";
+        let parts = read_all(multipart(body)).unwrap();
+        assert_eq!(parts, vec![
+            (Some("a".to_string()), None, "first".to_string()),
+            (Some("b".to_string()), None, "second\r\nline".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn quoted_parameters() {
+        let body = "--XyZzy\r\n\
+            Content-Disposition: form-data; name=\"x;y\"; filename=\"a;b \\\"c\\\".txt\"\r\n\r\n\
+            data\r\n--XyZzy--\r\n";
+        let parts = read_all(multipart(body)).unwrap();
+        assert_eq!(parts, vec![(Some("x;y".to_string()), Some("a;b \"c\".txt".to_string()), "data".to_string())]);
+    }
+
+    #[test]
+    fn part_limit() {
+        let part = "--XyZzy\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n";
+        let body = format!("{}--XyZzy--\r\n", part.repeat(3));
+        let limits = MultipartLimits { max_parts: 2, ..MultipartLimits::default() };
+
+        let err = read_all(multipart(&body).with_limits(limits.clone())).unwrap_err();
+        assert!(matches!(err, MultipartError::TooManyParts(2)), "{:?}", err);
+
+        let body = format!("{}--XyZzy--\r\n", part.repeat(2));
+        assert_eq!(read_all(multipart(&body).with_limits(limits)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn total_limit() {
+        let body = format!("--XyZzy\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n{}\r\n--XyZzy--\r\n", "x".repeat(64 * 1024));
+        let limits = MultipartLimits { max_total_size: 16 * 1024, ..MultipartLimits::default() };
+        let err = read_all(multipart(&body).with_limits(limits)).unwrap_err();
+        assert!(matches!(err, MultipartError::TooLarge(limit) if limit == 16 * 1024), "{:?}", err);
+        assert_eq!(err.status(), Status::ContentTooLarge);
+    }
+
+    #[test]
+    fn missing_closing_delimiter() {
+        let body = "--XyZzy\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue that never ends";
+        let err = read_all(multipart(body)).unwrap_err();
+        assert!(matches!(&err, MultipartError::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof), "{:?}", err);
+        assert_eq!(err.status(), Status::BadRequest);
+
+        let body = "--XyZzy\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue\r\n--XyZzy";
+        assert!(read_all(multipart(body)).is_err());
+    }
+}
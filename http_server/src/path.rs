@@ -1,13 +1,17 @@
-use std::fmt::Debug;
+use std::{convert::Infallible, fmt::{Debug, Display}};
 
-pub trait Path : Sized + Debug where Self::Error: Debug {
+/// A request target parsed from the request line.
+///
+/// Parse errors are shown to the client in the `400 Bad Request` body, so
+/// their `Display` should read as a sentence.
+pub trait Path : Sized + Debug where Self::Error: Debug + Display {
     type Error;
 
     fn parse<S: AsRef<str>>(path: S) -> Result<Self, Self::Error>;
 }
 
 impl Path for String {
-    type Error = ();
+    type Error = Infallible;
 
     fn parse<S: AsRef<str>>(path: S) -> Result<Self, Self::Error> {
         Ok(path.as_ref().to_string())
@@ -0,0 +1,72 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc::{self, Receiver, SyncSender}, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed set of worker threads fed through a bounded queue.
+///
+/// `execute` blocks once `queue_depth` jobs are waiting, which pushes back on
+/// the accept loop instead of buffering an unbounded number of connections.
+pub struct ThreadPool {
+    workers: Vec<JoinHandle<()>>,
+    sender: Option<SyncSender<Job>>,
+}
+
+impl ThreadPool {
+    pub fn new(threads: usize, queue_depth: usize) -> Self {
+        assert!(threads > 0, "A thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::sync_channel(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..threads)
+            .map(|id| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("http-worker-{}", id))
+                    .spawn(move || worker_loop(receiver))
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        ThreadPool { workers, sender: Some(sender) }
+    }
+
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        if let Some(sender) = &self.sender {
+            // Only fails once every worker is gone, in which case there is nobody left to run the job
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+fn worker_loop(receiver: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+
+        match job {
+            // Handler panics are answered where the request is served, anything else that panics
+            // must not take the worker down with it
+            Ok(job) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Closing the channel lets every worker finish its queue and exit
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
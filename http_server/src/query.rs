@@ -1,31 +1,202 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, error::Error, fmt::{Debug, Display}};
 
 use crate::path::Path;
 
+/// A query string that is not valid percent-encoded UTF-8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// A `%` not followed by two hex digits, with the offending sequence
+    InvalidEscape(String),
+    /// The decoded bytes are not valid UTF-8
+    InvalidUtf8,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidEscape(sequence) => write!(f, "Invalid percent-encoded sequence {:?}", sequence),
+            DecodeError::InvalidUtf8 => write!(f, "Percent-encoded data is not valid UTF-8"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes `%XX` escapes in `input`, and `+` as a space when `plus_as_space` is set
+pub fn percent_decode(input: &str, plus_as_space: bool) -> Result<String, DecodeError> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let escape = bytes.get(i + 1).and_then(|&b| hex_value(b))
+                    .zip(bytes.get(i + 2).and_then(|&b| hex_value(b)));
+                match escape {
+                    Some((high, low)) => decoded.push(high << 4 | low),
+                    None => {
+                        let end = (i + 3).min(bytes.len());
+                        let sequence = String::from_utf8_lossy(&bytes[i..end]).into_owned();
+                        return Err(DecodeError::InvalidEscape(sequence));
+                    }
+                }
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+/// Escapes every byte of `input` outside the unreserved set, so it can be used as a path segment
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for &b in input.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Parses `application/x-www-form-urlencoded` data, as found in query strings.
+///
+/// Every value of a repeated key is kept, in the order they appear.
+pub fn parse_urlencoded(input: &str) -> Result<HashMap<String, Vec<String>>, DecodeError> {
+    let mut pairs: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in input.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        pairs
+            .entry(percent_decode(key, true)?)
+            .or_default()
+            .push(percent_decode(value, true)?);
+    }
+    Ok(pairs)
+}
+
+/// Failure to parse a `QueryPath`, either in the path or in the query
+#[derive(Debug)]
+pub enum QueryPathError<E: Debug + Display> {
+    Path(E),
+    Query(DecodeError),
+}
+
+impl <E: Debug + Display> Display for QueryPathError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryPathError::Path(err) => write!(f, "Invalid path: {}", err),
+            QueryPathError::Query(err) => write!(f, "Invalid query: {}", err),
+        }
+    }
+}
+
+impl <E: Debug + Display> Error for QueryPathError<E> {}
+
 #[derive(Debug)]
 pub struct QueryPath<P: Path> {
     pub path: P,
-    pub query: HashMap<String, String>,
+    pub query: HashMap<String, Vec<String>>,
+}
+
+impl <P: Path> QueryPath<P> {
+    /// The first value of the query parameter `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).first().map(String::as_str)
+    }
+
+    /// Every value of the query parameter `key`, in the order they appear
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.query.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
 }
 
 impl <P: Path> Path for QueryPath<P> {
-    type Error = ();
+    type Error = QueryPathError<P::Error>;
 
     fn parse<S: AsRef<str>>(path: S) -> Result<Self, Self::Error> {
         let path = path.as_ref();
-        let mut split_path = path.splitn(2, "?");
-        let path = split_path
-            .next()
-            .unwrap();
-        let path = P::parse(path)
-            .unwrap();
-        let query = split_path
-            .flat_map(|s| s.split("&"))
-            .map(|s| s.splitn(2, "="))
-            .map(|mut s| (s.next().unwrap(), s.next().unwrap_or("")))
-            .map(|(k,v)| (k.to_string(), v.to_string()));
-        let query: HashMap<String, String> = query.collect();
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let path = P::parse(path).map_err(QueryPathError::Path)?;
+        let query = parse_urlencoded(query).map_err(QueryPathError::Query)?;
 
         Ok(QueryPath { path, query })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_plus_as_space() {
+        assert_eq!(percent_decode("a+b%20c", true).unwrap(), "a b c");
+        assert_eq!(percent_decode("a+b%20c", false).unwrap(), "a+b c");
+        assert_eq!(percent_decode("%2B", true).unwrap(), "+");
+    }
+
+    #[test]
+    fn keeps_repeated_keys_in_order() {
+        let query = parse_urlencoded("tag=b&x=1&tag=a&tag=c").unwrap();
+        assert_eq!(query["tag"], ["b", "a", "c"]);
+        assert_eq!(query["x"], ["1"]);
+    }
+
+    #[test]
+    fn rejects_truncated_escapes() {
+        assert_eq!(percent_decode("a%4", true), Err(DecodeError::InvalidEscape("%4".to_string())));
+        assert_eq!(percent_decode("a%", true), Err(DecodeError::InvalidEscape("%".to_string())));
+        assert_eq!(percent_decode("%zz1", true), Err(DecodeError::InvalidEscape("%zz".to_string())));
+        assert_eq!(parse_urlencoded("key=%4"), Err(DecodeError::InvalidEscape("%4".to_string())));
+    }
+
+    #[test]
+    fn rejects_invalid_utf8() {
+        assert_eq!(percent_decode("%C3%A9", true).unwrap(), "é");
+        assert_eq!(percent_decode("%C3", true), Err(DecodeError::InvalidUtf8));
+        assert_eq!(parse_urlencoded("%FF=1"), Err(DecodeError::InvalidUtf8));
+    }
+
+    #[test]
+    fn empty_keys_and_values() {
+        let query = parse_urlencoded("flag&empty=&=value&&").unwrap();
+        assert_eq!(query["flag"], [""]);
+        assert_eq!(query["empty"], [""]);
+        assert_eq!(query[""], ["value"]);
+        assert_eq!(query.len(), 3);
+        assert!(parse_urlencoded("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn query_path_access() {
+        let path = QueryPath::<String>::parse("/search?q=a+b&q=c").unwrap();
+        assert_eq!(path.path, "/search");
+        assert_eq!(path.get("q"), Some("a b"));
+        assert_eq!(path.get_all("q"), ["a b", "c"]);
+        assert!(path.get_all("missing").is_empty());
+    }
+
+    #[test]
+    fn readable_errors() {
+        let err = QueryPath::<String>::parse("/search?q=%zz").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid query: Invalid percent-encoded sequence \"%zz\"");
+    }
+}
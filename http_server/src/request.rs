@@ -1,57 +1,290 @@
-use std::collections::HashMap;
+use std::{cell::OnceCell, error::Error, fmt::Display, io};
 
+use crate::body::RequestBody;
+use crate::cookie::CookieJar;
+use crate::header::HeaderMap;
 use crate::path::Path;
+use crate::response::{HttpVersion, Status};
 
 #[derive(Debug)]
+pub enum ParseError {
+    /// Reading the request off the connection failed
+    Io(io::Error),
+    /// The request contained bytes that are not valid UTF-8
+    InvalidEncoding,
+    RequestLineTooLong,
+    /// The header section has too many fields or too many bytes
+    HeadersTooLarge,
+    /// The declared body is larger than the server accepts
+    BodyTooLarge(u64),
+    InvalidRequestLine(String),
+    InvalidHeader(String),
+    InvalidPath(String),
+    InvalidContentLength(String),
+    UnsupportedVersion(String),
+}
+
+impl ParseError {
+    /// The status the server answers this error with
+    pub fn status(&self) -> Status {
+        match self {
+            ParseError::RequestLineTooLong => Status::UriTooLong,
+            ParseError::HeadersTooLarge => Status::RequestHeaderFieldsTooLarge,
+            ParseError::BodyTooLarge(_) => Status::ContentTooLarge,
+            ParseError::UnsupportedVersion(_) => Status::HttpVersionNotSupported,
+            _ => Status::BadRequest,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "Failed to read the request: {}", err),
+            ParseError::InvalidEncoding => write!(f, "The request is not valid UTF-8"),
+            ParseError::RequestLineTooLong => write!(f, "The request line is too long"),
+            ParseError::HeadersTooLarge => write!(f, "The request headers are too large"),
+            ParseError::BodyTooLarge(limit) => write!(f, "The request body is larger than {} bytes", limit),
+            ParseError::InvalidRequestLine(line) => write!(f, "Invalid request line {:?}", line),
+            ParseError::InvalidHeader(line) => write!(f, "Invalid header line {:?}", line),
+            ParseError::InvalidPath(err) => write!(f, "Invalid request path: {}", err),
+            ParseError::InvalidContentLength(value) => write!(f, "Invalid Content-Length {:?}", value),
+            ParseError::UnsupportedVersion(version) => write!(f, "Unsupported HTTP version {:?}", version),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+/// Whether `string` is a token, the grammar of methods and field names (RFC 9110 section 5.6.2)
+pub(crate) fn is_token(string: &str) -> bool {
+    !string.is_empty() && string.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// The major and minor numbers of an `HTTP/x.y` version
+fn parse_http_version(version: &str) -> Option<(u8, u8)> {
+    let bytes = version.as_bytes();
+    let valid = bytes.len() == 8
+        && bytes.starts_with(b"HTTP/")
+        && bytes[5].is_ascii_digit()
+        && bytes[6] == b'.'
+        && bytes[7].is_ascii_digit();
+    valid.then(|| (bytes[5] - b'0', bytes[7] - b'0'))
+}
+
+/// A request method, as registered by RFC 9110, or any extension method
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
     Custom(String),
 }
 
 impl Method {
-    pub fn from_str<S: AsRef<str>>(string: S) -> Method {
-        match string.as_ref() {
-            "GET" => Method::Get,
-            string => Method::Custom(string.to_string()),
+    /// Every method with a variant of its own
+    pub const STANDARD: [Method; 9] = [
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+        Method::Connect,
+        Method::Options,
+        Method::Trace,
+        Method::Patch,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+            Method::Custom(method) => method,
         }
     }
+
+    /// Safe methods do not change anything on the server
+    pub fn is_safe(&self) -> bool {
+        matches!(self, Method::Get | Method::Head | Method::Options | Method::Trace)
+    }
+
+    /// Idempotent methods have the same effect however many times they are repeated
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, Method::Put | Method::Delete)
+    }
+}
+
+impl From<&str> for Method {
+    /// Methods are case-sensitive, so `get` is an extension method rather than `GET`
+    fn from(method: &str) -> Self {
+        Method::STANDARD
+            .into_iter()
+            .find(|standard| standard.as_str() == method)
+            .unwrap_or_else(|| Method::Custom(method.to_string()))
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Formats the methods served at a path as the value of an `Allow` header.
+///
+/// `HEAD` is added wherever `GET` is served, and `OPTIONS` always is.
+pub fn allow_header(methods: &[Method]) -> String {
+    let mut methods = methods.to_vec();
+    if methods.contains(&Method::Get) && !methods.contains(&Method::Head) {
+        methods.push(Method::Head);
+    }
+    if !methods.contains(&Method::Options) {
+        methods.push(Method::Options);
+    }
+
+    let methods: Vec<_> = methods.iter().map(|method| method.as_str()).collect();
+    methods.join(", ")
 }
 
 #[derive(Debug)]
-pub struct HttpRequest<P: Path> {
-    pub http_version: String,
+pub struct HttpRequest<'a, P: Path> {
+    pub http_version: HttpVersion,
     pub path: P,
     pub method: Method,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
+    pub body: RequestBody<'a>,
+    cookies: OnceCell<CookieJar>,
 }
 
-impl <P: Path> HttpRequest<P> {
-    pub fn parse_request(status_line: String, header_lines: Vec<String>) -> Self {
+impl <'a, P: Path> HttpRequest<'a, P> {
+    pub fn parse_request(status_line: String, header_lines: Vec<Vec<u8>>) -> Result<Self, ParseError> {
         // Parse status line
-        let mut status_line = status_line.splitn(2, " ");
-        let method = status_line.next().unwrap();
-        let method = Method::from_str(method);
-        let status_line = status_line.next().unwrap();
-        let mut status_line = status_line.rsplitn(2, " ");
-        let http_version = status_line.next().unwrap().to_string();
-        let path = status_line.next().unwrap().to_string();
-        let path = P::parse(path).unwrap();
+        let invalid_request_line = || ParseError::InvalidRequestLine(status_line.clone());
+        let mut parts = status_line.split(' ');
+        let (method, path, http_version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(path), Some(http_version), None) => (method, path, http_version),
+            _ => return Err(invalid_request_line()),
+        };
+        if !is_token(method) || path.is_empty() {
+            return Err(invalid_request_line());
+        }
+        let http_version = match parse_http_version(http_version) {
+            Some((1, 0)) => HttpVersion::Http1_0,
+            // Later minor versions are compatible with the highest one we know
+            Some((1, _)) => HttpVersion::Http1_1,
+            Some(_) => return Err(ParseError::UnsupportedVersion(http_version.to_string())),
+            None => return Err(invalid_request_line()),
+        };
+
+        let method = Method::from(method);
+        let path = P::parse(path).map_err(|err| ParseError::InvalidPath(err.to_string()))?;
 
         // Parse headers
-        let mut headers = HashMap::new();
+        let mut headers = HeaderMap::new();
         for line in header_lines.into_iter() {
-            let mut line = line.splitn(2, ": ");
-            let key = line.next().unwrap();
-            let value = line.next().unwrap();
-            headers.insert(key.to_string(), value.to_string());
+            let invalid_header = || ParseError::InvalidHeader(String::from_utf8_lossy(&line).into_owned());
+            let colon = line.iter().position(|&b| b == b':').ok_or_else(invalid_header)?;
+            let key = std::str::from_utf8(&line[..colon]).map_err(|_| invalid_header())?;
+            if !is_token(key) {
+                return Err(invalid_header());
+            }
+            // Values are kept as bytes, they may legally hold more than ASCII
+            headers.append(key, line[colon + 1..].trim_ascii());
         }
 
-        HttpRequest { 
+        Ok(HttpRequest { 
             http_version, 
             path, 
             method, 
-            headers
+            headers,
+            body: RequestBody::empty(),
+            cookies: OnceCell::new(),
+        })
+    }
+
+    /// The first value of the header `name`, if it is valid UTF-8
+    pub fn header<S: AsRef<str>>(&self, name: S) -> Option<&str> {
+        self.headers.get_str(name)
+    }
+
+    /// The cookies sent with the request, parsed the first time they are asked for
+    pub fn cookies(&self) -> &CookieJar {
+        self.cookies.get_or_init(|| CookieJar::from_headers(&self.headers))
+    }
+
+    /// The value of the cookie `name`
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies().get(name)
+    }
+
+    /// Comma separated values of every field called `name`
+    fn header_list<'b>(&'b self, name: &'b str) -> impl Iterator<Item = String> + 'b {
+        self.headers
+            .get_all(name)
+            .flat_map(|value| String::from_utf8_lossy(value.as_bytes())
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .collect::<Vec<_>>())
+            .filter(|item| !item.is_empty())
+    }
+
+    pub fn content_length(&self) -> Result<Option<u64>, ParseError> {
+        let mut content_length = None;
+        // A repeated Content-Length is only acceptable if every copy agrees
+        for length in self.header_list("Content-Length") {
+            let parsed = match length.bytes().all(|b| b.is_ascii_digit()) {
+                true => length.parse().ok(),
+                false => None,
+            };
+            match (parsed, content_length) {
+                (Some(parsed), None) => content_length = Some(parsed),
+                (Some(parsed), Some(previous)) if parsed == previous => (),
+                _ => return Err(ParseError::InvalidContentLength(length)),
+            }
         }
+        Ok(content_length)
+    }
+
+    /// Every transfer coding applied to the body, in the order they were applied
+    pub fn transfer_encoding(&self) -> Option<String> {
+        let codings: Vec<_> = self.header_list("Transfer-Encoding").collect();
+        match codings.is_empty() {
+            true => None,
+            false => Some(codings.join(", ")),
+        }
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        for option in self.header_list("Connection") {
+            if option.eq_ignore_ascii_case("close") {
+                return false;
+            }
+            if option.eq_ignore_ascii_case("keep-alive") {
+                return true;
+            }
+        }
+
+        // HTTP/1.1 connections are persistent by default, HTTP/1.0 ones are not
+        self.http_version >= HttpVersion::Http1_1
     }
 }
 
@@ -0,0 +1,271 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::query::percent_decode;
+use crate::response::{ErrorResponse, Status};
+
+/// How symbolic links under the root are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Follow links wherever they point
+    Follow,
+    /// Follow links as long as their target is under the root
+    #[default]
+    WithinRoot,
+    /// Refuse any path that goes through a link
+    Deny,
+}
+
+/// Why a request path does not lead to a file that can be served
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The path tries to leave the root or is otherwise not allowed, with the reason
+    Forbidden(&'static str),
+    /// Nothing is served at the path
+    NotFound,
+}
+
+impl ErrorResponse for ResolveError {
+    fn status(&self) -> Status {
+        match self {
+            ResolveError::Forbidden(_) => Status::Forbidden,
+            ResolveError::NotFound => Status::NotFound,
+        }
+    }
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::Forbidden(reason) => write!(f, "403 Forbidden\n\nReason: {}", reason),
+            ResolveError::NotFound => write!(f, "404 Not Found"),
+        }
+    }
+}
+
+impl Error for ResolveError {}
+
+impl From<io::Error> for ResolveError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => ResolveError::Forbidden("The file cannot be read"),
+            _ => ResolveError::NotFound,
+        }
+    }
+}
+
+/// Maps request paths onto files under a root directory, refusing anything
+/// that would reach outside of it.
+///
+/// Paths are percent-decoded one segment at a time. `..` segments, NUL bytes
+/// and encoded separators are rejected rather than interpreted.
+#[derive(Debug, Clone)]
+pub struct PathResolver<P: AsRef<Path> = PathBuf> {
+    root: P,
+    symlinks: SymlinkPolicy,
+    hide_dotfiles: bool,
+}
+
+impl <P: AsRef<Path>> PathResolver<P> {
+    pub fn new(root: P) -> Self {
+        PathResolver { root, symlinks: SymlinkPolicy::default(), hide_dotfiles: false }
+    }
+
+    pub fn root(&self) -> &Path {
+        self.root.as_ref()
+    }
+
+    pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Answers as if files and directories starting with a dot did not exist
+    pub fn with_hidden_dotfiles(mut self, hide_dotfiles: bool) -> Self {
+        self.hide_dotfiles = hide_dotfiles;
+        self
+    }
+
+    /// The decoded segments of `request_path`, checked one by one
+    fn segments(&self, request_path: &str) -> Result<Vec<String>, ResolveError> {
+        let Some(request_path) = request_path.strip_prefix('/') else {
+            return Err(ResolveError::NotFound);
+        };
+
+        let mut segments = Vec::new();
+        for segment in request_path.split('/') {
+            let segment = percent_decode(segment, false).map_err(|_| ResolveError::Forbidden("The path is not validly encoded"))?;
+            match segment.as_str() {
+                "" | "." => continue,
+                ".." => return Err(ResolveError::Forbidden("The path leads outside the server sandbox")),
+                _ => (),
+            }
+            if segment.contains(['/', '\\']) {
+                return Err(ResolveError::Forbidden("The path holds an encoded separator"));
+            }
+            if segment.contains('\0') {
+                return Err(ResolveError::Forbidden("The path holds a NUL byte"));
+            }
+            // Anything the platform would read as more than a file name, like a drive prefix
+            if !matches!(Path::new(&segment).components().collect::<Vec<_>>()[..], [Component::Normal(_)]) {
+                return Err(ResolveError::Forbidden("The path holds an invalid segment"));
+            }
+            if self.hide_dotfiles && segment.starts_with('.') {
+                return Err(ResolveError::NotFound);
+            }
+            segments.push(segment);
+        }
+        Ok(segments)
+    }
+
+    /// The file or directory `request_path` refers to.
+    ///
+    /// Fails with `NotFound` if nothing exists there, or `Forbidden` if the
+    /// path is malformed or breaks the symlink policy.
+    pub fn resolve(&self, request_path: &str) -> Result<PathBuf, ResolveError> {
+        let root = self.root.as_ref();
+        let mut path = root.to_path_buf();
+        for segment in self.segments(request_path)? {
+            path.push(segment);
+            if self.symlinks == SymlinkPolicy::Deny && fs::symlink_metadata(&path)?.file_type().is_symlink() {
+                return Err(ResolveError::Forbidden("The path goes through a symbolic link"));
+            }
+        }
+
+        match self.symlinks {
+            SymlinkPolicy::WithinRoot => {
+                if !fs::canonicalize(&path)?.starts_with(fs::canonicalize(root)?) {
+                    return Err(ResolveError::Forbidden("The path leads outside the server sandbox"));
+                }
+            }
+            SymlinkPolicy::Follow | SymlinkPolicy::Deny => {
+                fs::metadata(&path)?;
+            }
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{env, os::unix::fs::symlink, process, sync::atomic::{AtomicU64, Ordering}};
+
+    use super::*;
+
+    /// A directory tree under the system's temporary directory, removed on drop:
+    ///
+    /// ```text
+    /// outside.txt
+    /// root/
+    ///     file.txt
+    ///     .secret
+    ///     sub/page.txt
+    ///     inner -> sub/page.txt
+    ///     escape -> ../outside.txt
+    /// ```
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new() -> Self {
+            static COUNTER: AtomicU64 = AtomicU64::new(0);
+            let dir = env::temp_dir().join(format!("http-resolve-{}-{}", process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+            let root = dir.join("root");
+            fs::create_dir_all(root.join("sub")).unwrap();
+            fs::write(dir.join("outside.txt"), "outside").unwrap();
+            fs::write(root.join("file.txt"), "file").unwrap();
+            fs::write(root.join(".secret"), "secret").unwrap();
+            fs::write(root.join("sub/page.txt"), "page").unwrap();
+            symlink("sub/page.txt", root.join("inner")).unwrap();
+            symlink("../outside.txt", root.join("escape")).unwrap();
+            TestDir(dir)
+        }
+
+        fn resolver(&self) -> PathResolver {
+            PathResolver::new(self.0.join("root"))
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn is_forbidden(result: Result<PathBuf, ResolveError>) -> bool {
+        matches!(result, Err(ResolveError::Forbidden(_)))
+    }
+
+    #[test]
+    fn resolves_paths_under_the_root() {
+        let dir = TestDir::new();
+        let resolver = dir.resolver();
+        assert_eq!(resolver.resolve("/file.txt").unwrap(), dir.0.join("root/file.txt"));
+        assert_eq!(resolver.resolve("/sub//./page%2Etxt").unwrap(), dir.0.join("root/sub/page.txt"));
+        assert_eq!(resolver.resolve("/").unwrap(), dir.0.join("root"));
+        assert_eq!(resolver.resolve("/missing"), Err(ResolveError::NotFound));
+        assert_eq!(resolver.resolve("file.txt"), Err(ResolveError::NotFound));
+    }
+
+    #[test]
+    fn rejects_parent_segments() {
+        let dir = TestDir::new();
+        let resolver = dir.resolver();
+        assert!(is_forbidden(resolver.resolve("/../outside.txt")));
+        assert!(is_forbidden(resolver.resolve("/sub/../file.txt")));
+        assert!(is_forbidden(resolver.resolve("/%2e%2e/outside.txt")));
+        assert!(is_forbidden(resolver.resolve("/sub/%2E%2E/%2e%2e/outside.txt")));
+    }
+
+    #[test]
+    fn rejects_encoded_separators_and_nul() {
+        let dir = TestDir::new();
+        let resolver = dir.resolver();
+        assert!(is_forbidden(resolver.resolve("/sub%2fpage.txt")));
+        assert!(is_forbidden(resolver.resolve("/sub%2Fpage.txt")));
+        assert!(is_forbidden(resolver.resolve("/..%2foutside.txt")));
+        assert!(is_forbidden(resolver.resolve("/sub%5cpage.txt")));
+        assert!(is_forbidden(resolver.resolve("/file.txt%00.html")));
+        assert!(is_forbidden(resolver.resolve("/%zz")));
+    }
+
+    #[test]
+    fn hides_dotfiles() {
+        let dir = TestDir::new();
+        assert!(dir.resolver().resolve("/.secret").is_ok());
+
+        let resolver = dir.resolver().with_hidden_dotfiles(true);
+        assert_eq!(resolver.resolve("/.secret"), Err(ResolveError::NotFound));
+        assert_eq!(resolver.resolve("/%2Esecret"), Err(ResolveError::NotFound));
+        assert!(resolver.resolve("/file.txt").is_ok());
+    }
+
+    #[test]
+    fn follow_policy() {
+        let dir = TestDir::new();
+        let resolver = dir.resolver().with_symlink_policy(SymlinkPolicy::Follow);
+        assert!(resolver.resolve("/inner").is_ok());
+        assert!(resolver.resolve("/escape").is_ok());
+    }
+
+    #[test]
+    fn within_root_policy() {
+        let dir = TestDir::new();
+        let resolver = dir.resolver();
+        assert!(resolver.resolve("/inner").is_ok());
+        assert!(is_forbidden(resolver.resolve("/escape")));
+    }
+
+    #[test]
+    fn deny_policy() {
+        let dir = TestDir::new();
+        let resolver = dir.resolver().with_symlink_policy(SymlinkPolicy::Deny);
+        assert!(is_forbidden(resolver.resolve("/inner")));
+        assert!(is_forbidden(resolver.resolve("/escape")));
+        assert!(resolver.resolve("/sub/page.txt").is_ok());
+    }
+}
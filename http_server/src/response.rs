@@ -1,110 +1,317 @@
-use std::{collections::HashMap, net::TcpStream, io::Write};
+use std::{fmt::Display, io::{self, Read, Write}, net::TcpStream};
+
+use crate::chunked::ChunkedEncoder;
+use crate::cookie::SetCookie;
+use crate::header::{HeaderMap, HeaderValue};
+
+pub use crate::status::Status;
 
 pub trait HttpBody {
-    fn write_headers(&self, headers: &mut HashMap<String, String>);
+    fn write_headers(&self, headers: &mut HeaderMap);
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()>;
 
-    fn write_body(&self, stream: &mut TcpStream);
+    /// Writes the body straight to the client's socket, for bodies that can
+    /// send themselves without copying through user space
+    fn send_body(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        self.write_body(stream)
+    }
+
+    /// Called with the version of the response before anything is written,
+    /// for bodies whose framing depends on what the client understands
+    fn negotiate(&mut self, _http_version: HttpVersion) {}
 }
 
 impl HttpBody for String {
-    fn write_headers(&self, headers: &mut HashMap<String, String>) {
-        let len = self.bytes().len();
-        headers.insert("Content-Length".to_string(), len.to_string());
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let len = self.len();
+        headers.insert("Content-Length", len.to_string());
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+/// A body of unknown length, sent with `Transfer-Encoding: chunked` as it is produced.
+///
+/// The chunks can come from any iterator, including a `Receiver` fed by
+/// another thread, or from a `Read` implementation.
+///
+/// HTTP/1.0 clients know nothing of chunks, so they get the data as is and
+/// the end of the body is marked by closing the connection, without trailers.
+pub struct ChunkedBody {
+    chunks: Box<dyn Iterator<Item = io::Result<Vec<u8>>>>,
+    trailers: HeaderMap,
+    chunked: bool,
+}
+
+impl ChunkedBody {
+    pub fn new<I, T>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'static,
+        T: Into<Vec<u8>>,
+    {
+        ChunkedBody {
+            chunks: Box::new(chunks.into_iter().map(|chunk| Ok(chunk.into()))),
+            trailers: HeaderMap::new(),
+            chunked: true,
+        }
+    }
+
+    pub fn from_reader<R: Read + 'static>(mut reader: R) -> Self {
+        let mut buffer = vec![0; 8 * 1024];
+        let chunks = std::iter::from_fn(move || match reader.read(&mut buffer) {
+            Ok(0) => None,
+            Ok(read) => Some(Ok(buffer[..read].to_vec())),
+            Err(err) => Some(Err(err)),
+        });
+
+        ChunkedBody {
+            chunks: Box::new(chunks),
+            trailers: HeaderMap::new(),
+            chunked: true,
+        }
     }
 
-    fn write_body(&self, stream: &mut TcpStream) {
-        stream.write_all(self.as_bytes()).unwrap();
+    /// Adds a trailer field, sent after the last chunk
+    pub fn trailer<K: Into<String>, V: Into<HeaderValue>>(mut self, key: K, value: V) -> Self {
+        self.trailers.append(key, value);
+        self
     }
 }
 
-// impl HttpBody for String {
-//     fn write_headers(&self, headers: &mut HashMap<String, String>) {
-//         headers.insert("Transfer-Encoding".to_string(), "chunked".to_string());
-//     }
-// 
-//     fn write_body(&self, stream: &mut TcpStream) {
-//         ...
-//     }
-// }
+impl HttpBody for ChunkedBody {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.remove("Content-Length");
+        if !self.chunked {
+            headers.remove("Transfer-Encoding");
+            return;
+        }
+        headers.insert("Transfer-Encoding", "chunked");
+        if !self.trailers.is_empty() {
+            let names: Vec<_> = self.trailers.iter().map(|(key, _)| key).collect();
+            headers.insert("Trailer", names.join(", "));
+        }
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        if !self.chunked {
+            for chunk in self.chunks.as_mut() {
+                writer.write_all(&chunk?)?;
+            }
+            return Ok(());
+        }
+
+        let mut encoder = ChunkedEncoder::new(writer);
+        for chunk in self.chunks.as_mut() {
+            // Bailing out without the last chunk tells the client the body is incomplete
+            encoder.write_all(&chunk?)?;
+        }
+        encoder.finish(&self.trailers)?;
+        Ok(())
+    }
+
+    fn negotiate(&mut self, http_version: HttpVersion) {
+        self.chunked = http_version >= HttpVersion::Http1_1;
+    }
+}
 
 impl <B: HttpBody> HttpBody for Box<B> {
-    fn write_body(&self, stream: &mut TcpStream) {
-        self.as_ref().write_body(stream);
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        self.as_mut().write_body(writer)
     }
 
-    fn write_headers(&self, headers: &mut HashMap<String, String>) {
+    fn send_body(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        self.as_mut().send_body(stream)
+    }
+
+    fn write_headers(&self, headers: &mut HeaderMap) {
         self.as_ref().write_headers(headers);
     }
+
+    fn negotiate(&mut self, http_version: HttpVersion) {
+        self.as_mut().negotiate(http_version);
+    }
 }
 
 impl HttpBody for Box<dyn HttpBody> {
-    fn write_body(&self, stream: &mut TcpStream) {
-        self.as_ref().write_body(stream);
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        self.as_mut().write_body(writer)
     }
 
-    fn write_headers(&self, headers: &mut HashMap<String, String>) {
-        self.as_ref().write_headers(headers);
+    fn send_body(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        self.as_mut().send_body(stream)
     }
-}
 
-pub enum HttpVersion {
-    Http1_1,
-}
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        self.as_ref().write_headers(headers);
+    }
 
-impl HttpVersion {
-    pub fn to_string(&self) -> String {
-        match self {
-            HttpVersion::Http1_1 => "HTTP/1.1",
-        }.into()
+    fn negotiate(&mut self, http_version: HttpVersion) {
+        self.as_mut().negotiate(http_version);
     }
 }
 
-pub enum Status {
-    Ok,
-    NotFound,
-    Custom(usize, String),
+/// A version of HTTP/1, ordered so a response can be downgraded to what the client speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HttpVersion {
+    Http1_0,
+    Http1_1,
 }
 
-impl Status {
-    pub fn code(&self) -> usize {
+impl Display for HttpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Status::Ok => 200,
-            Status::NotFound => 404,
-            Status::Custom(code, _) => code.clone(),
+            HttpVersion::Http1_0 => write!(f, "HTTP/1.0"),
+            HttpVersion::Http1_1 => write!(f, "HTTP/1.1"),
         }
     }
-
-    pub fn message(&self) -> String {
-        match self {
-            Status::Ok => "Ok",
-            Status::NotFound => "Not Found",
-            Status::Custom(_, message) => message,
-        }.into()
-    }
 }
 
 pub struct HttpResponse<B: HttpBody> {
     http_version: HttpVersion,
     status: Status,
-    headers: HashMap<String, String>,
+    headers: HeaderMap,
     body: B,
 }
 
 impl <B: HttpBody> HttpResponse<B> {
-    pub fn new(http_version: HttpVersion, status: Status, headers: HashMap<String, String>, body: B) -> Self {
+    pub fn new(http_version: HttpVersion, status: Status, headers: HeaderMap, body: B) -> Self {
         HttpResponse { http_version, status, headers, body }
     }
 
-    pub fn write(&mut self, stream: &mut TcpStream) {
-        let status_line = format!("{} {} {}\r\n", self.http_version.to_string(), &self.status.code(), &self.status.message());
-        stream.write_all(status_line.as_bytes()).unwrap();
+    /// Takes the response apart, for turning it into a response of another type
+    pub fn into_parts(self) -> (HttpVersion, Status, HeaderMap, B) {
+        (self.http_version, self.status, self.headers, self.body)
+    }
+
+    pub fn http_version(&self) -> HttpVersion {
+        self.http_version
+    }
+
+    pub fn set_http_version(&mut self, http_version: HttpVersion) {
+        self.http_version = http_version;
+    }
+
+    pub fn header<S: AsRef<str>>(&self, name: S) -> Option<&HeaderValue> {
+        self.headers.get(name)
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    pub fn set_header<K: Into<String>, V: Into<HeaderValue>>(&mut self, key: K, value: V) {
+        self.headers.insert(key, value);
+    }
+
+    /// Adds a `Set-Cookie` field for `cookie`, keeping the ones already set
+    pub fn add_cookie(&mut self, cookie: SetCookie) {
+        self.headers.append("Set-Cookie", cookie.to_string());
+    }
 
+    /// Writes the status line and headers, leaving out the body as for a `HEAD` request
+    pub fn write_head<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.body.negotiate(self.http_version);
         self.body.write_headers(&mut self.headers);
+        if !self.status.allows_body() {
+            self.headers.remove("Content-Length");
+            self.headers.remove("Transfer-Encoding");
+        } else if !self.headers.contains("Content-Length") && !self.headers.contains("Transfer-Encoding") {
+            // Without either the only way to tell where the body ends is closing the connection
+            self.headers.insert("Connection", "close");
+        }
+        self.headers.validate()?;
+
+        let mut head = format!("{} {}\r\n", self.http_version, &self.status).into_bytes();
         for (key, value) in self.headers.iter() {
-            stream.write_all(format!("{}: {}\r\n", key, value).as_bytes()).unwrap();
+            head.extend_from_slice(key.as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        writer.write_all(&head)
+    }
+
+    pub fn write<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.write_head(writer)?;
+        if self.status.allows_body() {
+            self.body.write_body(writer)?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the response to a socket, letting the body send itself without copying where it can
+    pub fn send(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        self.write_head(stream)?;
+        if self.status.allows_body() {
+            self.body.send_body(stream)?;
         }
-        stream.write_all("\r\n".as_bytes()).unwrap();
-        
-        self.body.write_body(stream);
+        stream.flush()
+    }
+}
+
+/// An error that answers a request by itself, as a plain text response carrying its message.
+///
+/// Handlers can return such errors with `?` through the `From` impls below.
+pub trait ErrorResponse: Display {
+    fn status(&self) -> Status;
+
+    fn to_response(&self) -> HttpResponse<String> {
+        let headers = HeaderMap::from([("Content-Type", "text/plain; charset=utf-8")]);
+        HttpResponse::new(HttpVersion::Http1_1, self.status(), headers, self.to_string())
+    }
+}
+
+impl <E: ErrorResponse> From<E> for HttpResponse<String> {
+    fn from(err: E) -> Self {
+        err.to_response()
+    }
+}
+
+impl <E: ErrorResponse> From<E> for HttpResponse<Box<dyn HttpBody>> {
+    fn from(err: E) -> Self {
+        let (http_version, status, headers, body) = err.to_response().into_parts();
+        HttpResponse::new(http_version, status, headers, Box::new(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_head(headers: HeaderMap) -> io::Result<Vec<u8>> {
+        let mut response = HttpResponse::new(HttpVersion::Http1_1, Status::Ok, headers, String::new());
+        let mut head = Vec::new();
+        response.write_head(&mut head)?;
+        Ok(head)
+    }
+
+    #[test]
+    fn writes_headers() {
+        let head = write_head(HeaderMap::from([("X-Note", "a\tb")])).unwrap();
+        assert_eq!(head, b"HTTP/1.1 200 OK\r\nX-Note: a\tb\r\nContent-Length: 0\r\n\r\n");
+    }
+
+    #[test]
+    fn rejects_header_injection() {
+        for value in ["a\r\nSet-Cookie: x=y", "a\nb", "a\rb", "a\0b", "a\x7Fb"] {
+            let err = write_head(HeaderMap::from([("X-Note", value)])).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", value);
+        }
+        for name in ["", "X Note", "X-Note:", "X-Note\r\nSet-Cookie"] {
+            let err = write_head(HeaderMap::from([(name, "value")])).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_trailers() {
+        let trailers = HeaderMap::from([("X-Checksum", "a\r\n\r\nHTTP/1.1 200 OK")]);
+        let err = ChunkedEncoder::new(Vec::new()).finish(&trailers).map(|_| ()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
     }
 }
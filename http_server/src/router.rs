@@ -1,10 +1,55 @@
-use std::collections::HashMap;
-use std::hash::Hash;
-use crate::query::QueryPath;
-use crate::request::HttpRequest;
+use crate::path::Path;
+use crate::request::{HttpRequest, Method};
 use crate::response::{HttpResponse, HttpBody};
 
-pub type RequestHandler<P, B> = fn(HttpRequest<P>) -> HttpResponse<B>;
+pub trait RequestHandler<P: Path, B: HttpBody> {
+    fn handle(&self, request: HttpRequest<'_, P>) -> HttpResponse<B>;
+
+    /// Overrides the server's body size limit for this request, before any of the body is read
+    fn max_body_size(&self, _request: &HttpRequest<'_, P>) -> Option<u64> {
+        None
+    }
+
+    /// The methods the handler serves at the request's path.
+    ///
+    /// When this returns a list the server answers `OPTIONS` requests itself
+    /// with a matching `Allow` header; otherwise they reach `handle`.
+    fn allowed_methods(&self, _request: &HttpRequest<'_, P>) -> Option<Vec<Method>> {
+        None
+    }
+}
+
+impl <P: Path, B: HttpBody, F: Fn(HttpRequest<'_, P>) -> HttpResponse<B>> RequestHandler<P, B> for F {
+    fn handle(&self, request: HttpRequest<'_, P>) -> HttpResponse<B> {
+        self(request)
+    }
+}
+
+/// Wraps a request handler with its own body size limit
+pub struct BodyLimit<H> {
+    handler: H,
+    max_body_size: u64,
+}
+
+impl <H> BodyLimit<H> {
+    pub fn new(handler: H, max_body_size: u64) -> Self {
+        BodyLimit { handler, max_body_size }
+    }
+}
+
+impl <P: Path, B: HttpBody, H: RequestHandler<P, B>> RequestHandler<P, B> for BodyLimit<H> {
+    fn handle(&self, request: HttpRequest<'_, P>) -> HttpResponse<B> {
+        self.handler.handle(request)
+    }
+
+    fn max_body_size(&self, _request: &HttpRequest<'_, P>) -> Option<u64> {
+        Some(self.max_body_size)
+    }
+
+    fn allowed_methods(&self, request: &HttpRequest<'_, P>) -> Option<Vec<Method>> {
+        self.handler.allowed_methods(request)
+    }
+}
 
 /*
 pub enum HierarchicalKey {
@@ -0,0 +1,293 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::ServerConfig,
+    handle_stream,
+    path::Path,
+    pool::ThreadPool,
+    response::HttpBody,
+    router::RequestHandler,
+};
+
+/// Reads from a socket, failing with a timeout once an overall deadline has passed.
+///
+/// A per-read timeout alone lets a client trickle one byte at a time forever.
+pub(crate) struct TimedReader {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl TimedReader {
+    pub fn new(stream: TcpStream) -> Self {
+        TimedReader { stream, deadline: None }
+    }
+
+    /// Every read fails once `deadline` has passed
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    /// Every read fails after `timeout` without receiving anything, however long the whole takes
+    pub fn set_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        self.deadline = None;
+        self.stream.set_read_timeout(Some(timeout))
+    }
+}
+
+impl Read for TimedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "Read deadline exceeded"));
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+        }
+
+        self.stream.read(buf)
+    }
+}
+
+/// Checks whether a read failed because a timeout ran out
+pub(crate) fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
+struct OpenConnection {
+    stream: TcpStream,
+    idle: bool,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    open: HashMap<u64, OpenConnection>,
+}
+
+/// Book-keeping shared between the accept loop, the workers and the server handle.
+#[derive(Default)]
+pub(crate) struct Connections {
+    shutting_down: AtomicBool,
+    registry: Mutex<Registry>,
+    closed: Condvar,
+}
+
+impl Connections {
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn register(self: &Arc<Self>, stream: &TcpStream) -> io::Result<Connection> {
+        let stream = stream.try_clone()?;
+        let mut registry = self.registry();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.open.insert(id, OpenConnection { stream, idle: true });
+
+        Ok(Connection { id, connections: Arc::clone(self) })
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Closes every connection waiting for a request, or all of them when `force` is set
+    fn close(&self, force: bool) {
+        let registry = self.registry();
+        for connection in registry.open.values() {
+            if force || connection.idle {
+                let _ = connection.stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    /// Waits until every connection is closed, returning false if the deadline passes first
+    fn wait_closed(&self, deadline: Instant) -> bool {
+        let mut registry = self.registry();
+        while !registry.open.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+
+            registry = match self.closed.wait_timeout(registry, deadline - now) {
+                Ok((registry, _)) => registry,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        }
+        true
+    }
+}
+
+/// A connection registered with the server, unregistered when dropped
+pub(crate) struct Connection {
+    id: u64,
+    connections: Arc<Connections>,
+}
+
+impl Connection {
+    /// Marks the connection as waiting for its next request.
+    ///
+    /// Returns false if the server is shutting down, in which case the
+    /// connection should be closed instead.
+    pub fn idle(&self) -> bool {
+        let mut registry = self.connections.registry();
+        if self.connections.is_shutting_down() {
+            return false;
+        }
+        if let Some(connection) = registry.open.get_mut(&self.id) {
+            connection.idle = true;
+        }
+        true
+    }
+
+    /// Marks the connection as processing a request
+    pub fn busy(&self) {
+        if let Some(connection) = self.connections.registry().open.get_mut(&self.id) {
+            connection.idle = false;
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.connections.is_shutting_down()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.connections.registry().open.remove(&self.id);
+        self.connections.closed.notify_all();
+    }
+}
+
+pub struct Server;
+
+impl Server {
+    /// Binds to `addr` and starts serving requests on a background thread.
+    ///
+    /// Fails with `InvalidInput` if `config` has no worker threads or allows no requests per connection.
+    pub fn bind<A, P, B, H>(addr: A, request_handler: H, config: ServerConfig) -> io::Result<ServerHandle>
+    where
+        A: ToSocketAddrs,
+        P: Path + 'static,
+        B: HttpBody + 'static,
+        H: RequestHandler<P, B> + Send + Sync + 'static,
+    {
+        config.validate()?;
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let connections = Arc::new(Connections::default());
+
+        let acceptor = {
+            let connections = Arc::clone(&connections);
+            thread::Builder::new()
+                .name("http-acceptor".to_string())
+                .spawn(move || accept_loop(listener, request_handler, config, connections))?
+        };
+
+        Ok(ServerHandle { local_addr, connections, acceptor })
+    }
+}
+
+/// How long accepting waits after its first failure, doubling on each failure in a row up to the max
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+fn accept_loop<P, B, H>(listener: TcpListener, request_handler: H, config: ServerConfig, connections: Arc<Connections>)
+where
+    P: Path + 'static,
+    B: HttpBody + 'static,
+    H: RequestHandler<P, B> + Send + Sync + 'static,
+{
+    let pool = ThreadPool::new(config.worker_threads, config.queue_depth);
+    let request_handler = Arc::new(request_handler);
+    let config = Arc::new(config);
+    let mut backoff = MIN_ACCEPT_BACKOFF;
+
+    for stream in listener.incoming() {
+        if connections.is_shutting_down() {
+            break;
+        }
+
+        let stream = match stream {
+            Ok(stream) => {
+                backoff = MIN_ACCEPT_BACKOFF;
+                stream
+            }
+            Err(_) => {
+                // Errors like running out of file descriptors persist until connections close,
+                // retrying right away would only spin
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+
+        let connection = match connections.register(&stream) {
+            Ok(connection) => connection,
+            Err(_) => continue,
+        };
+
+        let request_handler = Arc::clone(&request_handler);
+        let config = Arc::clone(&config);
+        pool.execute(move || handle_stream(stream, request_handler.as_ref(), &config, &connection));
+    }
+
+    // Dropping the pool waits for the workers to finish their connections
+}
+
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    connections: Arc<Connections>,
+    acceptor: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops the server.
+    ///
+    /// New connections are refused and idle keep-alive connections are closed
+    /// right away. Requests in flight get until `grace_period` runs out to
+    /// finish, after which their connections are closed as well. Returns once
+    /// every worker has exited.
+    pub fn shutdown(self, grace_period: Duration) {
+        let deadline = Instant::now() + grace_period;
+
+        self.connections.shutting_down.store(true, Ordering::SeqCst);
+        self.wake_acceptor();
+        self.connections.close(false);
+
+        if !self.connections.wait_closed(deadline) {
+            self.connections.close(true);
+        }
+
+        let _ = self.acceptor.join();
+    }
+
+    /// Blocks until the server stops
+    pub fn wait(self) {
+        let _ = self.acceptor.join();
+    }
+
+    /// Unblocks the accept loop so it can notice the shutdown
+    fn wake_acceptor(&self) {
+        let mut addr = self.local_addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+};
+
+use crate::autoindex::{self, Listing, Sort};
+use crate::file::FileBody;
+use crate::header::HeaderMap;
+use crate::json::Json;
+use crate::mime::MimeTypes;
+use crate::resolve::{PathResolver, ResolveError, SymlinkPolicy};
+use crate::response::{ErrorResponse, HttpBody, HttpResponse, HttpVersion, Status};
+
+/// The body of a static file response
+#[derive(Debug)]
+pub enum StaticBody {
+    File(FileBody),
+    /// A directory listing or an error message, with its `Content-Type` among the response headers
+    Text(String),
+    Json(Json),
+}
+
+impl HttpBody for StaticBody {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        match self {
+            StaticBody::File(file) => file.write_headers(headers),
+            StaticBody::Text(text) => text.write_headers(headers),
+            StaticBody::Json(json) => json.write_headers(headers),
+        }
+    }
+
+    fn write_body(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        match self {
+            StaticBody::File(file) => file.write_body(writer),
+            StaticBody::Text(text) => text.write_body(writer),
+            StaticBody::Json(json) => json.write_body(writer),
+        }
+    }
+
+    fn send_body(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        match self {
+            StaticBody::File(file) => file.send_body(stream),
+            StaticBody::Text(text) => text.send_body(stream),
+            StaticBody::Json(json) => json.send_body(stream),
+        }
+    }
+}
+
+/// What a static file request is answered with, for a router to turn into its own response type
+#[derive(Debug)]
+pub struct StaticResponse {
+    pub status: Status,
+    pub headers: HeaderMap,
+    pub body: StaticBody,
+}
+
+impl StaticResponse {
+    fn error(err: ResolveError) -> Self {
+        let (_, status, headers, body) = err.to_response().into_parts();
+        StaticResponse { status, headers, body: StaticBody::Text(body) }
+    }
+}
+
+impl From<StaticResponse> for HttpResponse<Box<dyn HttpBody>> {
+    fn from(response: StaticResponse) -> Self {
+        HttpResponse::new(HttpVersion::Http1_1, response.status, response.headers, Box::new(response.body))
+    }
+}
+
+/// Serves the files under a root directory.
+///
+/// Directories are answered with their `index.html`, or with a listing of
+/// their contents when autoindex is on.
+#[derive(Debug, Clone)]
+pub struct StaticFiles<P: AsRef<Path> = PathBuf> {
+    resolver: PathResolver<P>,
+    mime_types: MimeTypes,
+    autoindex: bool,
+}
+
+impl <P: AsRef<Path>> StaticFiles<P> {
+    pub fn new(root: P) -> Self {
+        StaticFiles { resolver: PathResolver::new(root), mime_types: MimeTypes::new(), autoindex: false }
+    }
+
+    pub fn resolver(&self) -> &PathResolver<P> {
+        &self.resolver
+    }
+
+    /// Sets how symbolic links under the root are treated, only following those that stay under it by default
+    pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.resolver = self.resolver.with_symlink_policy(symlinks);
+        self
+    }
+
+    /// Answers with `404 Not Found` for files and directories starting with a dot
+    pub fn with_hidden_dotfiles(mut self, hide_dotfiles: bool) -> Self {
+        self.resolver = self.resolver.with_hidden_dotfiles(hide_dotfiles);
+        self
+    }
+
+    /// Replaces the table `Content-Type` is picked from
+    pub fn with_mime_types(mut self, mime_types: MimeTypes) -> Self {
+        self.mime_types = mime_types;
+        self
+    }
+
+    /// Lists the contents of directories without an `index.html`, as HTML or as JSON when the client asks for it
+    pub fn with_autoindex(mut self, autoindex: bool) -> Self {
+        self.autoindex = autoindex;
+        self
+    }
+
+    /// Answers a `GET` of `request_path`.
+    ///
+    /// `query` picks the order of listings and `headers` whether they are sent as JSON.
+    pub fn respond(&self, request_path: &str, query: &HashMap<String, Vec<String>>, headers: &HeaderMap) -> StaticResponse {
+        let path = match self.resolve_file(request_path) {
+            Ok(path) => path,
+            Err(ResolveError::NotFound) if self.autoindex => return self.listing(request_path, query, headers),
+            Err(err) => return StaticResponse::error(err),
+        };
+
+        match FileBody::open(&path) {
+            Ok(file) => StaticResponse {
+                status: Status::Ok,
+                headers: HeaderMap::from([("Content-Type", self.mime_types.content_type(&path))]),
+                body: StaticBody::File(file),
+            },
+            Err(err) => StaticResponse::error(ResolveError::from(err)),
+        }
+    }
+
+    /// The file to serve for `request_path`, which is the `index.html` inside for directories
+    fn resolve_file(&self, request_path: &str) -> Result<PathBuf, ResolveError> {
+        let path = self.resolver.resolve(request_path)?;
+        if !path.is_dir() {
+            return Ok(path);
+        }
+        self.resolver.resolve(&format!("{}/index.html", request_path.trim_end_matches('/')))
+    }
+
+    /// The listing of the directory at `request_path`, or `404 Not Found` if there is none
+    fn listing(&self, request_path: &str, query: &HashMap<String, Vec<String>>, headers: &HeaderMap) -> StaticResponse {
+        let mut listing = match Listing::read(&self.resolver, request_path) {
+            Ok(listing) => listing,
+            Err(err) => return StaticResponse::error(err),
+        };
+        let sort = Sort::from_query(query);
+        listing.sort(sort);
+
+        // The same URL answers with either representation
+        let mut response_headers = HeaderMap::from([("Vary", "Accept")]);
+        if autoindex::wants_json(headers) {
+            let body = StaticBody::Json(Json::new(listing.to_json()));
+            return StaticResponse { status: Status::Ok, headers: response_headers, body };
+        }
+        response_headers.insert("Content-Type", "text/html; charset=utf-8");
+        StaticResponse { status: Status::Ok, headers: response_headers, body: StaticBody::Text(listing.to_html(sort)) }
+    }
+}